@@ -31,7 +31,11 @@
             openssl
           ];
 
-          buildInputs = with pkgs; [ openssl ];
+          # alsa-lib is only linked with the `sound` feature
+          buildInputs = with pkgs; [
+            openssl
+            alsa-lib
+          ];
 
           LD_LIBRARY_PATH = pkgs.lib.makeLibraryPath buildInputs;
 
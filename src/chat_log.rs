@@ -0,0 +1,79 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use chrono::{NaiveDate, Utc};
+use color_eyre::eyre;
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncWriteExt, BufWriter},
+    sync::broadcast,
+};
+
+use crate::event::Message;
+
+/// Appends chat messages to `{dir}/{channel}/{date}.log`, starting a new file at midnight UTC
+pub struct ChatLog {
+    dir: PathBuf,
+    /// Opened on the first message in a channel, along with the date they were opened for
+    files: HashMap<String, (NaiveDate, BufWriter<File>)>,
+}
+
+impl ChatLog {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            files: HashMap::new(),
+        }
+    }
+
+    pub async fn run(mut self, mut messages: broadcast::Receiver<Message>) {
+        loop {
+            match messages.recv().await {
+                Ok(message) => {
+                    if let Err(e) = self.write(&message).await {
+                        tracing::warn!(channel = message.channel, error = %e, "chat log failed");
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn write(&mut self, message: &Message) -> eyre::Result<()> {
+        let today = Utc::now().date_naive();
+        let file = match self.files.get_mut(&message.channel) {
+            Some((date, file)) if *date == today => file,
+            _ => {
+                let file = self.open(&message.channel, today).await?;
+                let (_, file) = self
+                    .files
+                    .entry(message.channel.clone())
+                    .insert_entry((today, file))
+                    .into_mut();
+                file
+            }
+        };
+
+        let line = format!(
+            "[{}] {}: {}\n",
+            message.timestamp.format("%H:%M:%S"),
+            message.username,
+            message.msg
+        );
+        file.write_all(line.as_bytes()).await?;
+        // Lines are flushed right away so nothing is lost when tuige exits
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn open(&self, channel: &str, date: NaiveDate) -> eyre::Result<BufWriter<File>> {
+        let dir = self.dir.join(channel.trim_start_matches('#'));
+        fs::create_dir_all(&dir).await?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("{date}.log")))
+            .await?;
+        Ok(BufWriter::new(file))
+    }
+}
@@ -1,7 +1,14 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+use chrono::format::{Item, StrftimeItems};
 use color_eyre::eyre;
 use eyre::OptionExt;
+use memchr::memmem::Finder;
 use serde::Deserialize;
 use triomphe::Arc;
 
@@ -11,16 +18,400 @@ pub struct ConfigData<'a> {
     // TODO: store token in keyring instead of config
     pub token: Cow<'a, str>,
     pub channels: Vec<Cow<'a, str>>,
+    /// Extra accounts, each with its own chat connection
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    /// Messages older than this are removed from the chat buffer
+    pub max_message_age_seconds: Option<u64>,
+    /// Number of emotes downloaded in parallel, defaults to 5 and is capped at 20
+    pub emote_download_concurrency: Option<usize>,
+    /// Shown at the top of every channel on startup
+    pub motd: Option<String>,
+    /// Serve the remote control api on localhost when set
+    pub grpc_port: Option<u16>,
+    /// Serve Prometheus metrics at `/metrics` on localhost when set
+    pub metrics_port: Option<u16>,
+    /// Blank line between messages
+    #[serde(default)]
+    pub message_gap: bool,
+    /// Keep the gift leaderboard across sessions
+    #[serde(default)]
+    pub persist_gift_stats: bool,
+    /// Idle connections kept open per host by the http client
+    pub http_pool_max_idle_per_host: Option<usize>,
+    /// Longer messages are cut off until expanded
+    pub max_message_display_length: Option<usize>,
+    /// Animate scrolling instead of jumping
+    #[serde(default)]
+    pub smooth_scroll: bool,
+    /// Capture the mouse for scrolling and clicking, turn off to select text with the terminal
+    #[serde(default = "default_true")]
+    pub mouse_capture: bool,
+    /// Categories shown in the stream browser
+    #[serde(default)]
+    pub watch_categories: Vec<Cow<'a, str>>,
+    /// Words highlighted as mentions, defaults to just `username`
+    pub mention_keywords: Option<Vec<String>>,
+    /// Words highlighted as mentions in addition to `mention_keywords`
+    #[serde(default)]
+    pub extra_mentions: Vec<String>,
+    /// Seconds before cached emotes are downloaded again, per provider
+    pub emote_ttl_twitch_seconds: Option<u64>,
+    pub emote_ttl_7tv_seconds: Option<u64>,
+    pub emote_ttl_bttv_seconds: Option<u64>,
+    pub emote_ttl_ffz_seconds: Option<u64>,
+    /// Repair messages that were double-encoded as UTF-8
+    #[serde(default)]
+    pub fix_encoding: bool,
+    /// Channels announced in the active tab when they go live
+    #[serde(default)]
+    pub team_members: Vec<String>,
+    /// Messages containing any of these, ignoring case, are highlighted like mentions
+    #[serde(default)]
+    pub highlight_keywords: Vec<String>,
+    /// Messages matching a rule are highlighted in its color, the first match wins
+    #[serde(default)]
+    pub highlights: Vec<HighlightRule>,
+    /// Shape of the terminal cursor in the message input, the input draws its own block cursor
+    /// when unset
+    pub cursor_style: Option<CursorStyle>,
+    /// Action names mapped to keys like `ctrl+q` or `alt+l`, replacing the action's default keys
+    #[serde(default)]
+    pub keybindings: HashMap<String, String>,
+    /// Chat messages from these users are dropped in every channel
+    #[serde(default)]
+    pub ignore_users: Vec<String>,
+    /// Users ignored in just one channel, keyed by channel name
+    #[serde(default)]
+    pub channel_ignore_users: HashMap<String, Vec<String>>,
+    /// Colors, a built-in theme picked by `name` with any colors set here on top
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    /// Only show chat messages from users with these badges, your own messages are always shown
+    pub show_only_type: Option<UserType>,
+    /// Initialization steps to run, in order, steps left out are skipped
+    #[serde(default = "default_startup_order")]
+    pub startup_order: Vec<StartupStep>,
+    /// `http://` or `socks5://` proxy for helix requests, either just the url or a `[proxy]`
+    /// section with credentials
+    pub proxy: Option<Proxy>,
+    /// Color cycle messages sent with a power-up
+    #[serde(default)]
+    pub show_power_up_animations: bool,
+    /// Darken every other message
+    #[serde(default = "default_true")]
+    pub alternating_rows: bool,
+    /// Chat messages matching a key exactly are answered with its value
+    #[serde(default)]
+    pub auto_responses: HashMap<String, String>,
+    /// Load BetterTTV emotes along with the twitch ones
+    #[serde(default = "default_true")]
+    pub bttv: bool,
+    /// Load FrankerFaceZ emotes along with the twitch ones
+    #[serde(default = "default_true")]
+    pub ffz: bool,
+    /// `1x`, `2x` or `3x`, for every emote provider
+    #[serde(default)]
+    pub emote_scale: EmoteScale,
+    /// Show the drops panel. Twitch's GQL api rejects most third party client ids, so it usually
+    /// only shows why the fetch failed
+    #[serde(default)]
+    pub drops: bool,
+    /// Download the animated variant of twitch emotes that have one
+    #[serde(default)]
+    pub prefer_animated_emotes: bool,
+    /// Chat is written to `{log_dir}/{channel}/{date}.log` when set
+    pub log_dir: Option<PathBuf>,
+    /// `.wav` or `.ogg` file played on mentions, needs the `sound` feature
+    pub mention_sound: Option<String>,
+    /// Show when each message was received
+    #[serde(default)]
+    pub show_timestamps: bool,
+    /// strftime format for timestamps, or `relative` for how long ago, defaults to `%H:%M`
+    #[serde(default = "default_timestamp_format")]
+    pub timestamp_format: String,
+    /// Chatters stay in the user list this long after their last message, defaults to 10
+    pub user_list_minutes: Option<u64>,
+    /// Columns taken by the user list when it's open
+    #[serde(default = "default_user_list_width")]
+    pub user_list_width: u16,
+    /// Columns taken by the channel list when it's open
+    #[serde(default = "default_sidebar_width")]
+    pub sidebar_width: u16,
+    /// Share of the terminal height above the message input, including the status bar, from 50
+    /// to 98
+    #[serde(default = "default_chat_height_percent")]
+    pub chat_height_percent: u8,
+    /// Wait before sending an auto-response, defaults to 500
+    pub autoresponse_delay_ms: Option<u64>,
+    /// A trigger is answered at most once this often per channel, defaults to 30
+    pub autoresponse_cooldown_seconds: Option<u64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// `timestamp_format` showing how long ago messages were sent, like `[12m]`
+pub const RELATIVE_TIMESTAMPS: &str = "relative";
+
+fn default_timestamp_format() -> String {
+    "%H:%M".into()
+}
+
+fn default_user_list_width() -> u16 {
+    24
+}
+
+fn default_sidebar_width() -> u16 {
+    20
+}
+
+fn default_chat_height_percent() -> u8 {
+    90
+}
+
+/// Where emotes come from
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Provider {
+    Twitch,
+    SevenTv,
+    Bttv,
+    Ffz,
+}
+
+impl Provider {
+    pub const ALL: [Self; 4] = [Self::Twitch, Self::SevenTv, Self::Bttv, Self::Ffz];
+}
+
+/// Size emote images are downloaded in, bigger ones look sharper when drawn as images
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmoteScale {
+    #[default]
+    #[serde(rename = "1x")]
+    One,
+    #[serde(rename = "2x")]
+    Two,
+    #[serde(rename = "3x")]
+    Three,
+}
+
+impl EmoteScale {
+    pub fn factor(self) -> u8 {
+        match self {
+            Self::One => 1,
+            Self::Two => 2,
+            Self::Three => 3,
+        }
+    }
+
+    /// Twitch and FFZ skip 3x and offer 4x instead
+    pub fn twitch_factor(self) -> u8 {
+        match self {
+            Self::Three => 4,
+            scale => scale.factor(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorStyle {
+    Block,
+    Underline,
+    Bar,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum UserType {
+    All,
+    /// Subscribers, moderators and the broadcaster
+    SubsOnly,
+    /// Moderators and the broadcaster
+    ModsOnly,
+}
+
+impl UserType {
+    pub fn allows(self, badges: &[String]) -> bool {
+        let has = |names: &[&str]| badges.iter().any(|badge| names.contains(&badge.as_str()));
+        match self {
+            Self::All => true,
+            Self::SubsOnly => has(&["subscriber", "founder", "moderator", "broadcaster"]),
+            Self::ModsOnly => has(&["moderator", "broadcaster"]),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Account {
+    pub username: String,
+    pub token: String,
+    /// Joined by this account unless the main account already joins them
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+/// Colors are `#rrggbb` or names like `red` or `light-blue`
+#[derive(Deserialize, Default)]
+pub struct ThemeConfig {
+    /// `catppuccin-mocha`, `nord` or `gruvbox-dark`
+    pub name: Option<String>,
+    /// Used for chatters without a Twitch color
+    pub username_color: Option<String>,
+    pub mention_bg: Option<String>,
+    pub row_alternate_bg: Option<String>,
+    pub system_event_fg: Option<String>,
+    pub border_color: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Proxy {
+    Url(String),
+    WithAuth {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+impl Proxy {
+    /// Credentials are put in the url, which works for both http and socks5 proxies
+    pub fn to_reqwest(&self) -> eyre::Result<reqwest::Proxy> {
+        Ok(match self {
+            Self::Url(url) => reqwest::Proxy::all(url)?,
+            Self::WithAuth {
+                url,
+                username,
+                password,
+            } => {
+                let mut url = reqwest::Url::parse(url)?;
+                if let Some(username) = username {
+                    url.set_username(username)
+                        .map_err(|()| eyre::eyre!("proxy url can't have a username"))?;
+                }
+                if let Some(password) = password {
+                    url.set_password(Some(password))
+                        .map_err(|()| eyre::eyre!("proxy url can't have a password"))?;
+                }
+                reqwest::Proxy::all(url)?
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HighlightRule {
+    /// Regular expression, prefix with `(?i)` to ignore case
+    pub pattern: String,
+    pub color: (u8, u8, u8),
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupStep {
+    /// Resolve the client and channel ids that helix requests and stream events need
+    ValidateToken,
+    JoinChannels,
+    FetchGlobalEmotes,
+    FetchChannelEmotes,
+}
+
+fn default_startup_order() -> Vec<StartupStep> {
+    vec![
+        StartupStep::ValidateToken,
+        StartupStep::JoinChannels,
+        StartupStep::FetchGlobalEmotes,
+        StartupStep::FetchChannelEmotes,
+    ]
+}
+
+impl ConfigData<'_> {
+    /// Whether `ignore_users` or the channel's `channel_ignore_users` contain `username`
+    pub fn ignores(&self, channel: &str, username: &str) -> bool {
+        let channel = channel.trim_start_matches('#');
+        self.ignore_users
+            .iter()
+            .chain(
+                self.channel_ignore_users
+                    .iter()
+                    .filter(|(name, _)| name.trim_start_matches('#').eq_ignore_ascii_case(channel))
+                    .flat_map(|(_, users)| users),
+            )
+            .any(|user| user.eq_ignore_ascii_case(username))
+    }
+
+    pub fn user_list_ttl(&self) -> Duration {
+        Duration::from_secs(self.user_list_minutes.unwrap_or(10) * 60)
+    }
+
+    pub fn emote_download_concurrency(&self) -> usize {
+        self.emote_download_concurrency.unwrap_or(5).clamp(1, 20)
+    }
+
+    /// Emote cache TTL for `provider`, `None` keeps emotes forever
+    pub fn emote_ttl(&self, provider: Provider) -> Option<Duration> {
+        let seconds = match provider {
+            Provider::Twitch => self.emote_ttl_twitch_seconds,
+            Provider::SevenTv => self.emote_ttl_7tv_seconds,
+            Provider::Bttv => self.emote_ttl_bttv_seconds,
+            Provider::Ffz => self.emote_ttl_ffz_seconds,
+        };
+        seconds.map(Duration::from_secs)
+    }
+
+    /// One finder per `mention_keywords` and `extra_mentions`, matching lowercase text
+    pub fn mention_finders(&self) -> Vec<Finder<'static>> {
+        let keywords = match &self.mention_keywords {
+            Some(keywords) => keywords.iter().map(String::as_str).collect(),
+            None => vec![self.username.as_ref()],
+        };
+        keywords
+            .into_iter()
+            .chain(self.extra_mentions.iter().map(String::as_str))
+            .filter(|keyword| !keyword.is_empty())
+            .map(|keyword| Finder::new(&keyword.to_lowercase()).into_owned())
+            .collect()
+    }
+
+    pub fn autoresponse_delay(&self) -> Duration {
+        Duration::from_millis(self.autoresponse_delay_ms.unwrap_or(500))
+    }
+
+    pub fn autoresponse_cooldown(&self) -> Duration {
+        Duration::from_secs(self.autoresponse_cooldown_seconds.unwrap_or(30))
+    }
 }
 
 pub type Config = Arc<ConfigData<'static>>;
 
-pub fn from_config_dir() -> eyre::Result<Config> {
-    let dir = dirs::config_dir()
+pub fn path() -> eyre::Result<PathBuf> {
+    Ok(dirs::config_dir()
         .ok_or_eyre("configuration file not found")?
-        .join("tuige/config.toml");
+        .join("tuige/config.toml"))
+}
 
-    Ok(Arc::new(
-        std::fs::read_to_string(&dir).map(|s| toml::from_str::<ConfigData>(&s))??,
-    ))
+pub fn load(path: &Path) -> eyre::Result<Config> {
+    let cfg = std::fs::read_to_string(path).map(|s| toml::from_str::<ConfigData>(&s))??;
+    if !(50..=98).contains(&cfg.chat_height_percent) {
+        eyre::bail!(
+            "chat_height_percent must be between 50 and 98, got {}",
+            cfg.chat_height_percent
+        );
+    }
+    // Formatting with an invalid format panics
+    if cfg.timestamp_format != RELATIVE_TIMESTAMPS
+        && StrftimeItems::new(&cfg.timestamp_format).any(|item| item == Item::Error)
+    {
+        eyre::bail!(
+            "timestamp_format {:?} isn't a valid strftime format",
+            cfg.timestamp_format
+        );
+    }
+    Ok(Arc::new(cfg))
+}
+
+pub fn from_config_dir() -> eyre::Result<Config> {
+    load(&path()?)
 }
@@ -0,0 +1,78 @@
+use color_eyre::eyre;
+use tokio::sync::broadcast;
+use zbus::{interface, SignalContext};
+
+use crate::{
+    config::Config,
+    event::{Message, MessageKind},
+};
+
+const PATH: &str = "/org/tuige/Notifier";
+
+struct Notifier;
+
+#[interface(name = "org.tuige.Notifier")]
+impl Notifier {
+    #[zbus(signal)]
+    async fn mention(
+        ctxt: &SignalContext<'_>,
+        channel: &str,
+        username: &str,
+        message: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn stream_live(ctxt: &SignalContext<'_>, channel: &str, title: &str) -> zbus::Result<()>;
+}
+
+/// Emits `org.tuige.Notifier` signals on the session bus
+#[derive(Clone)]
+pub struct Dbus {
+    conn: zbus::Connection,
+}
+
+impl Dbus {
+    pub async fn connect() -> eyre::Result<Self> {
+        let conn = zbus::connection::Builder::session()?
+            .name("org.tuige.Notifier")?
+            .serve_at(PATH, Notifier)?
+            .build()
+            .await?;
+
+        Ok(Self { conn })
+    }
+
+    pub async fn mention(&self, message: &Message) -> eyre::Result<()> {
+        let ctxt = SignalContext::new(&self.conn, PATH)?;
+        Notifier::mention(&ctxt, &message.channel, &message.username, &message.msg).await?;
+        Ok(())
+    }
+
+    pub async fn stream_live(&self, channel: &str, title: &str) -> eyre::Result<()> {
+        let ctxt = SignalContext::new(&self.conn, PATH)?;
+        Notifier::stream_live(&ctxt, channel, title).await?;
+        Ok(())
+    }
+
+    /// Emits a mention signal for every chat message containing one of the mention keywords
+    pub async fn watch_mentions(self, cfg: Config, mut messages: broadcast::Receiver<Message>) {
+        let finders = cfg.mention_finders();
+        loop {
+            match messages.recv().await {
+                Ok(message) => {
+                    let text = message.msg.to_lowercase();
+                    if message.kind == MessageKind::Chat
+                        && !message.username.eq_ignore_ascii_case(&cfg.username)
+                        && finders
+                            .iter()
+                            .any(|finder| finder.find(text.as_bytes()).is_some())
+                    {
+                        let _ = self.mention(&message).await;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
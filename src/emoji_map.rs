@@ -0,0 +1,44 @@
+/// Closest unicode emoji for a twitch global emote, used when the emote image can't be shown
+pub fn emoji(emote: &str) -> Option<&'static str> {
+    Some(match emote {
+        "<3" => "❤️",
+        "4Head" => "😄",
+        "BabyRage" => "😭",
+        "BibleThump" => "😢",
+        "bleedPurple" => "💜",
+        "BloodTrail" => "🩸",
+        "CoolCat" => "😎",
+        "CoolStoryBob" => "📖",
+        "CorgiDerp" => "🐶",
+        "DansGame" => "🤢",
+        "DinoDance" => "🦖",
+        "FailFish" => "🤦",
+        "GivePLZ" => "🙌",
+        "HeyGuys" => "👋",
+        "imGlitch" => "👾",
+        "Jebaited" => "🎣",
+        "Kappa" => "😐",
+        "KappaPride" => "🌈",
+        "Kreygasm" => "😩",
+        "LUL" => "😂",
+        "MrDestructoid" => "🤖",
+        "NotLikeThis" => "😫",
+        "OSFrog" => "🐸",
+        "PJSalt" => "🧂",
+        "PogChamp" => "😮",
+        "PopCorn" => "🍿",
+        "RaccAttack" => "🦝",
+        "ResidentSleeper" => "😴",
+        "SeemsGood" => "👍",
+        "SMOrc" => "👹",
+        "StinkyCheese" => "🧀",
+        "SwiftRage" => "😠",
+        "TakeNRG" => "🙌",
+        "TheIlluminati" => "🔺",
+        "TriHard" => "😁",
+        "TwitchUnity" => "🤝",
+        "VoHiYo" => "🙋",
+        "WutFace" => "😱",
+        _ => return None,
+    })
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use image::DynamicImage;
+use ratatui_image::{picker::Picker, protocol::StatefulProtocol};
+
+use crate::request::Emote;
+
+/// Emote images available for inline rendering
+pub struct Emotes {
+    /// `None` when the terminal can't display images
+    picker: Option<Picker>,
+    /// Frames of every emote, static emotes have one
+    images: HashMap<String, Vec<DynamicImage>>,
+    /// Frame delays of animated emotes
+    frame_delays: HashMap<String, Vec<u32>>,
+    /// Images of the frames drawn so far, shared by every chat
+    protocols: HashMap<String, Vec<Option<Box<dyn StatefulProtocol>>>>,
+}
+
+impl Emotes {
+    pub fn new(picker: Option<Picker>) -> Self {
+        Self {
+            picker,
+            images: HashMap::new(),
+            frame_delays: HashMap::new(),
+            protocols: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, emotes: Vec<Emote>) {
+        for emote in emotes {
+            self.protocols.remove(emote.name());
+            match emote {
+                Emote::Static { name, image } => {
+                    self.frame_delays.remove(&name);
+                    self.images.insert(name, vec![image]);
+                }
+                Emote::Animated(animation) => {
+                    self.frame_delays
+                        .insert(animation.name.clone(), animation.frame_delay_ms);
+                    self.images.insert(animation.name, animation.frames);
+                }
+            }
+        }
+    }
+
+    /// Whether `name` is a loaded emote, even if it can't be displayed
+    pub fn is_known(&self, name: &str) -> bool {
+        self.images.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.images.keys().map(String::as_str)
+    }
+
+    /// Whether `name` can be rendered as an image
+    pub fn contains(&self, name: &str) -> bool {
+        self.picker.is_some() && self.images.contains_key(name)
+    }
+
+    /// How long each frame of `name` is shown, `None` for static emotes
+    pub fn frame_delays(&self, name: &str) -> Option<&[u32]> {
+        self.frame_delays.get(name).map(Vec::as_slice)
+    }
+
+    /// Image of frame `index` of `name`, created the first time it's drawn
+    pub fn protocol(&mut self, name: &str, index: usize) -> Option<&mut Box<dyn StatefulProtocol>> {
+        let frames = self.images.get(name)?;
+        let picker = self.picker.as_mut()?;
+        if !self.protocols.contains_key(name) {
+            let protocols = frames.iter().map(|_| None).collect();
+            self.protocols.insert(name.to_string(), protocols);
+        }
+        let index = index % frames.len();
+        let protocol = &mut self.protocols.get_mut(name)?[index];
+        Some(protocol.get_or_insert_with(|| picker.new_resize_protocol(frames[index].clone())))
+    }
+}
@@ -0,0 +1,65 @@
+/// Piece of a chat message, emotes only ever match whole words
+#[derive(Debug, PartialEq)]
+pub enum Token<'a> {
+    Emote(&'a str),
+    Text(&'a str),
+}
+
+/// Splits `text` on whitespace and punctuation, matching whole tokens with `is_emote`.
+///
+/// Whitespace separated words are tried as a whole first, so emotes made of punctuation like
+/// `<3` still match.
+pub fn tokenize<'a>(text: &'a str, is_emote: impl Fn(&str) -> bool) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+
+    for (start, word) in split_keep_offsets(text, char::is_whitespace) {
+        if is_emote(word) {
+            push_emote(&mut tokens, text, &mut text_start, start, word);
+            continue;
+        }
+
+        for (offset, part) in split_keep_offsets(word, |c| !c.is_alphanumeric() && c != '_') {
+            if is_emote(part) {
+                push_emote(&mut tokens, text, &mut text_start, start + offset, part);
+            }
+        }
+    }
+
+    if text_start < text.len() {
+        tokens.push(Token::Text(&text[text_start..]));
+    }
+    tokens
+}
+
+fn push_emote<'a>(
+    tokens: &mut Vec<Token<'a>>,
+    text: &'a str,
+    text_start: &mut usize,
+    start: usize,
+    emote: &'a str,
+) {
+    if *text_start < start {
+        tokens.push(Token::Text(&text[*text_start..start]));
+    }
+    tokens.push(Token::Emote(emote));
+    *text_start = start + emote.len();
+}
+
+/// Non-empty runs of characters between separators, along with their byte offset
+fn split_keep_offsets(
+    text: &str,
+    is_separator: impl Fn(char) -> bool,
+) -> impl Iterator<Item = (usize, &str)> {
+    let mut start = None;
+    text.char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+        .filter_map(move |(i, c)| {
+            if i == text.len() || is_separator(c) {
+                start.take().map(|start| (start, &text[start..i]))
+            } else {
+                start.get_or_insert(i);
+                None
+            }
+        })
+}
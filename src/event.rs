@@ -1,41 +1,459 @@
 use core::panic;
+use std::{
+    collections::HashMap,
+    future::Future,
+    time::{Duration, Instant},
+};
 
+use chrono::{DateTime, Local};
 use color_eyre::eyre;
 use crossterm::event::EventStream;
+use eyre::OptionExt;
 use futures::{future::Fuse, stream::Next, FutureExt, StreamExt};
+use indexmap::IndexMap;
 use tokio::{
     select,
-    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    sync::{
+        broadcast,
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+    },
 };
+use triomphe::Arc;
 
 use crate::{
-    config::Config,
-    request::{Cache, Value},
+    chat_log::ChatLog,
+    config::{self, Config, Provider, StartupStep},
+    eventsub::{Broadcaster, EventSub},
+    grpc::Remote,
+    metrics,
+    request::{Cache, Emote, Value},
 };
 
 #[derive(Clone, PartialEq, PartialOrd)]
 pub struct Message {
+    /// Twitch message id, `None` for messages that never went through Twitch
+    pub id: Option<String>,
     pub channel: String,
     pub username: String,
     pub msg: String,
+    pub kind: MessageKind,
+    /// Sent with a power-up animation
+    pub power_up: bool,
+    /// IRC line the message was parsed from
+    pub raw: Option<String>,
+    /// When the message was received or sent
+    pub timestamp: DateTime<Local>,
+    /// Username color chosen by the sender
+    pub color: Option<(u8, u8, u8)>,
+    /// Badge names such as `subscriber` or `moderator`
+    pub badges: Vec<String>,
+    /// Extra account the message was received or sent through, `None` for the main account
+    pub account: Option<String>,
+}
+
+impl Message {
+    /// Message received or sent now, without an id, badges or anything else from Twitch
+    pub fn new(channel: String, username: String, msg: String, kind: MessageKind) -> Self {
+        Self {
+            id: None,
+            channel,
+            username,
+            msg,
+            kind,
+            power_up: false,
+            raw: None,
+            timestamp: Local::now(),
+            color: None,
+            badges: Vec::new(),
+            account: None,
+        }
+    }
+
+    /// Message generated by tuige itself rather than sent by a chatter
+    pub fn system(channel: String, msg: String) -> Self {
+        Self::new(channel, "[tuige]".into(), msg, MessageKind::System)
+    }
+}
+
+#[derive(Clone, PartialEq, PartialOrd)]
+pub struct CharityCampaign {
+    pub charity: String,
+    pub current: f64,
+    pub target: f64,
+    pub currency: String,
+}
+
+/// State of a background task, shown in the task panel
+#[derive(Clone, PartialEq, PartialOrd)]
+pub enum TaskState {
+    Running,
+    Retrying,
+    Finished,
+    Failed(String),
+}
+
+/// Adds a third party emote set to `emotes`, failures are only logged
+fn merge_emote_set(emotes: &mut Vec<Emote>, provider: Provider, set: eyre::Result<&Value>) {
+    match set {
+        Ok(Value::EmoteSet(set)) => emotes.extend(set.iter().cloned()),
+        Ok(_) => tracing::warn!(?provider, "expected emote set"),
+        Err(e) => tracing::warn!(?provider, error = %e, "failed to load emotes"),
+    }
+}
+
+/// Config read by the config watcher. Configs can't be compared, so one is only equal to itself
+pub struct ReloadedConfig(pub Config);
+
+impl PartialEq for ReloadedConfig {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for ReloadedConfig {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self == other).then_some(std::cmp::Ordering::Equal)
+    }
+}
+
+/// Settings of one chat connection
+struct ChatConnection {
+    /// Extra account the connection is logged in as, `None` for the main account
+    account: Option<String>,
+    /// Lowercase logins of the other configured accounts
+    other_accounts: Vec<String>,
+    fix_encoding: bool,
+}
+
+impl ChatConnection {
+    /// `logins` are all configured accounts, lowercase
+    fn new(account: Option<String>, logins: &[String], fix_encoding: bool) -> Self {
+        let own = account
+            .as_deref()
+            .map_or_else(|| logins[0].clone(), str::to_lowercase);
+        Self {
+            other_accounts: logins
+                .iter()
+                .filter(|login| **login != own)
+                .cloned()
+                .collect(),
+            account,
+            fix_encoding,
+        }
+    }
+
+    fn is_other_account(&self, login: &str) -> bool {
+        self.other_accounts
+            .iter()
+            .any(|account| account.eq_ignore_ascii_case(login))
+    }
+}
+
+/// Reports the state of the background task `name`, the time of the report is its last activity
+pub fn report_task(event_tx: &UnboundedSender<ev::In>, name: &str, state: TaskState) {
+    let _ = event_tx.send(ev::In::Task {
+        name: name.into(),
+        state,
+        at: Local::now(),
+    });
 }
 
+/// Title and category shown in the status bar
+#[derive(Clone, PartialEq, PartialOrd)]
+pub struct StreamInfo {
+    pub channel: String,
+    pub title: String,
+    pub category: String,
+}
+
+#[derive(Clone, PartialEq, PartialOrd)]
+pub struct CompletedPrediction {
+    pub channel: String,
+    pub title: String,
+    /// `None` when the prediction was canceled
+    pub winner: Option<String>,
+    /// Top predictors as login, points bet and points won
+    pub predictors: Vec<(String, u64, u64)>,
+}
+
+/// Sub, gift sub or raid announced by a USERNOTICE
+#[derive(Clone, PartialEq, PartialOrd)]
+pub struct SystemEvent {
+    pub channel: String,
+    pub kind: SystemEventKind,
+}
+
+#[derive(Clone, PartialEq, PartialOrd)]
+pub enum SystemEventKind {
+    Sub {
+        user: String,
+        months: u64,
+    },
+    /// `gifter` is `None` for anonymous gifts
+    GiftSub {
+        gifter: Option<String>,
+        recipient: String,
+    },
+    MysteryGift {
+        gifter: Option<String>,
+        count: u64,
+    },
+    Raid {
+        raider: String,
+        viewers: u64,
+    },
+}
+
+impl SystemEvent {
+    pub fn text(&self) -> String {
+        match &self.kind {
+            SystemEventKind::Sub {
+                user,
+                months: 0 | 1,
+            } => format!("🎉 {user} just subscribed!"),
+            SystemEventKind::Sub { user, months } => {
+                format!("🎉 {user} just subscribed for {months} months!")
+            }
+            SystemEventKind::GiftSub { gifter, recipient } => format!(
+                "🎁 {} gifted a sub to {recipient}!",
+                gifter.as_deref().unwrap_or("An anonymous gifter")
+            ),
+            SystemEventKind::MysteryGift { gifter, count } => format!(
+                "🎁 {} is gifting {count} subs to the community!",
+                gifter.as_deref().unwrap_or("An anonymous gifter")
+            ),
+            SystemEventKind::Raid { raider, viewers } => {
+                format!("⚔ {raider} is raiding with {viewers} viewers!")
+            }
+        }
+    }
+
+    /// Shown in chat as a centered line
+    pub fn into_message(self) -> Message {
+        let mut message = Message::system(self.channel.clone(), self.text());
+        message.kind = MessageKind::UserNotice;
+        message
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum MessageKind {
+    Chat,
+    System,
+    /// Sub, gift sub or raid announcement
+    UserNotice,
+    /// Hype train contribution announcement
+    HypeTrain(ContributionSize),
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum ContributionSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl ContributionSize {
+    /// `total` is in bits, or sub points where a tier 1 sub is worth 500
+    pub fn from_total(total: u64) -> Self {
+        match total {
+            0..500 => Self::Small,
+            500..2500 => Self::Medium,
+            _ => Self::Large,
+        }
+    }
+}
+
+/// Editors often save in several steps, the config is reloaded once they settle
+const CONFIG_RELOAD_DELAY: Duration = Duration::from_millis(200);
+
 /// Events
 pub mod ev {
-    use super::Message;
+    use std::{path::PathBuf, time::Duration};
+
+    use chrono::{DateTime, Local};
+
+    use super::{
+        CharityCampaign, CompletedPrediction, Message, ReloadedConfig, StreamInfo, SystemEvent,
+        TaskState,
+    };
+    use crate::request::{
+        CacheStats, CustomReward, DropCampaign, Emote, LiveStream, ScheduleSegment, StreamStatus,
+    };
 
     /// Incoming events
     #[derive(PartialEq, PartialOrd)]
     pub enum In {
         Key(crossterm::event::KeyEvent),
+        Mouse(crossterm::event::MouseEvent),
         Message(Message),
+        ChannelUpdate {
+            channel: String,
+            title: Option<String>,
+            category: Option<String>,
+        },
+        PruneMessages,
+        Schedule {
+            channel: String,
+            segments: Vec<ScheduleSegment>,
+        },
+        Raid {
+            channel: String,
+            raider: String,
+            viewers: u64,
+        },
+        SystemEvent(SystemEvent),
+        /// A moderator deleted the message with the Twitch message id `id`
+        MessageDeleted {
+            channel: String,
+            id: String,
+        },
+        /// `user` was banned, or timed out for `timeout`
+        UserCleared {
+            channel: String,
+            user: String,
+            timeout: Option<Duration>,
+        },
+        /// Subs gifted by a named user
+        Gift {
+            channel: String,
+            gifter: String,
+            count: usize,
+        },
+        /// A channel from `team_members` went live
+        TeamMemberLive {
+            login: String,
+        },
+        PredictionEnd(CompletedPrediction),
+        StreamInfoUpdated(StreamInfo),
+        /// Stream delay in seconds, 0 for low latency
+        StreamDelay {
+            channel: String,
+            delay: u64,
+        },
+        /// Chat modes that changed, `None` for modes left unchanged
+        RoomState {
+            channel: String,
+            /// Seconds between messages, `Some(None)` when disabled
+            slow: Option<Option<u64>>,
+            sub_only: Option<bool>,
+            emote_only: Option<bool>,
+            /// Minutes followed, `Some(Some(0))` for any follower and `Some(None)` when disabled
+            followers_only: Option<Option<u64>>,
+        },
+        /// Uptime and viewers, `None` while offline
+        StreamStatus {
+            channel: String,
+            status: Option<StreamStatus>,
+        },
+        /// Direct message sent to us
+        Whisper {
+            sender: String,
+            text: String,
+        },
+        /// Single sub handed out as part of a mystery gift
+        CommunityGiftSub {
+            channel: String,
+            gifter: String,
+        },
+        /// Live streams in a watched category
+        CategoryStreams {
+            category: String,
+            streams: Vec<LiveStream>,
+        },
+        /// Charity campaign progress, `None` once the campaign ends
+        Charity {
+            channel: String,
+            campaign: Option<CharityCampaign>,
+        },
+        ChannelSuggestions {
+            query: String,
+            channels: Vec<String>,
+        },
+        Emotes(Vec<Emote>),
+        /// Drop campaigns running right now, or why they couldn't be fetched
+        Drops(Result<Vec<DropCampaign>, String>),
+        /// Custom channel points rewards of a channel
+        Rewards {
+            channel: String,
+            rewards: Vec<CustomReward>,
+        },
+        /// Number of EventSub subscriptions found not `enabled` in the last health check
+        EventSubHealth {
+            degraded: usize,
+        },
+        /// Feedback for something the user asked for, shown in the active tab
+        Notification(String),
+        /// Background task status changed or it did something
+        Task {
+            name: String,
+            state: TaskState,
+            at: DateTime<Local>,
+        },
         Redraw,
+        /// The config file changed and was parsed, or why it couldn't be. Tabs are opened and
+        /// closed to match its channels
+        ConfigReload(Result<ReloadedConfig, String>),
+        CacheStats(CacheStats),
     }
 
     /// Outgoing events
     #[derive(PartialEq, PartialOrd)]
     pub enum Send {
         Message(Message),
+        /// Message sent as a reply to the message with id `parent_id`
+        Reply {
+            message: Message,
+            parent_id: String,
+        },
+        Join(String),
+        Part(String),
+        /// Look up channels for `/join` completion
+        SearchChannels(String),
+        /// Write a channel's chat history as plain text to `path`
+        ExportLog {
+            channel: String,
+            path: PathBuf,
+            text: String,
+        },
+        /// Look up active drop campaigns
+        FetchDrops,
+        /// Count the disk cache and send back the cache statistics
+        FetchCacheStats,
+        /// Drop the channel's cached emote set and download it again
+        InvalidateCache(String),
+        /// Look up a channel's custom rewards
+        FetchRewards(String),
+        /// Enable or disable a custom reward, then look up the channel's rewards again
+        SetRewardEnabled {
+            channel: String,
+            reward_id: String,
+            enabled: bool,
+        },
+        /// Ban a user from a channel, or time them out for `duration` seconds
+        Ban {
+            channel: String,
+            username: String,
+            duration: Option<u32>,
+        },
+        /// Start a poll lasting `duration` seconds
+        CreatePoll {
+            channel: String,
+            title: String,
+            choices: Vec<String>,
+            duration: u32,
+        },
+        /// Run an ad break of `length` seconds
+        Commercial {
+            channel: String,
+            length: u32,
+        },
+        /// Grant or remove VIP status in a channel
+        Vip {
+            channel: String,
+            username: String,
+            vip: bool,
+        },
     }
 }
 
@@ -44,20 +462,42 @@ pub struct EventHandler {
     event_tx: UnboundedSender<ev::In>,
     cfg: Config,
     cache: Cache,
+    /// Resolved once on startup, helix requests are skipped without it
+    client_id: Option<String>,
+    /// Channel name to broadcaster id
+    broadcaster_ids: IndexMap<String, String>,
+}
+
+/// Credentials for helix requests on behalf of a channel
+struct BroadcasterAuth {
+    client_id: String,
+    token: String,
+    broadcaster_id: String,
+}
+
+/// What a background request needs from [`EventHandler`]
+struct Api {
+    event_tx: UnboundedSender<ev::In>,
+    cfg: Config,
+    cache: Cache,
+    client_id: Option<String>,
+    broadcaster_ids: IndexMap<String, String>,
 }
 
 impl EventHandler {
     pub fn new(
         cfg: Config,
-        disk_cache_dir: String,
+        cache: Cache,
         event_tx: UnboundedSender<ev::In>,
         handler_rx: UnboundedReceiver<ev::Send>,
     ) -> Self {
         Self {
             event_tx,
             handler_rx,
+            cache,
             cfg,
-            cache: Cache::new(disk_cache_dir),
+            client_id: None,
+            broadcaster_ids: IndexMap::default(),
         }
     }
 
@@ -65,9 +505,8 @@ impl EventHandler {
         let mut reader = crossterm::event::EventStream::new();
 
         let mut tmi_event_tx = self.event_tx.clone();
-        let cfg = self.cfg.clone();
+        let fix_encoding = self.cfg.fix_encoding;
 
-        //TODO: multiple clients per user
         let mut client = tmi::Client::builder()
             .credentials(tmi::Credentials {
                 login: self.cfg.username.to_string(),
@@ -77,25 +516,334 @@ impl EventHandler {
             .await?;
 
         let (message_tx, message_rx) = mpsc::unbounded_channel();
-        tokio::spawn(async move {
-            client.join_all(&cfg.channels).await.unwrap();
-            let mut message_rx = message_rx;
-            loop {
-                Self::tmi_event(&cfg, &mut client, &mut tmi_event_tx, &mut message_rx)
+        // Chat messages going both ways, for remote control subscribers
+        let (broadcast_tx, _) = broadcast::channel(100);
+        // Every account's own messages are shown when sent, not again when another account reads them
+        let logins = std::iter::once(self.cfg.username.to_string())
+            .chain(
+                self.cfg
+                    .accounts
+                    .iter()
+                    .map(|account| account.username.clone()),
+            )
+            .map(|login| login.to_lowercase())
+            .collect::<Vec<_>>();
+        {
+            let connection = ChatConnection::new(None, &logins, fix_encoding);
+            let broadcast_tx = broadcast_tx.clone();
+            report_task(&self.event_tx, "chat", TaskState::Running);
+            tokio::spawn(async move {
+                // Filled in by the join_channels startup step and `/join`
+                let mut channels = Vec::new();
+                let mut message_rx = message_rx;
+                loop {
+                    if let Err(e) = Self::tmi_event(
+                        &mut channels,
+                        &mut client,
+                        &mut tmi_event_tx,
+                        &mut message_rx,
+                        &broadcast_tx,
+                        &connection,
+                    )
                     .await
-                    .unwrap();
+                    {
+                        report_task(&tmi_event_tx, "chat", TaskState::Failed(e.to_string()));
+                        break;
+                    }
+                }
+            });
+        }
+
+        // Senders for the chat connection of each extra account
+        let mut account_txs = HashMap::new();
+        for account in &self.cfg.accounts {
+            let (account_tx, account_rx) = mpsc::unbounded_channel();
+            account_txs.insert(account.username.clone(), account_tx);
+            let channels = account
+                .channels
+                .iter()
+                .filter(|channel| {
+                    !self
+                        .cfg
+                        .channels
+                        .iter()
+                        .any(|main| main.eq_ignore_ascii_case(channel))
+                })
+                .cloned()
+                .collect();
+            tokio::spawn(Self::run_account(
+                ChatConnection::new(Some(account.username.clone()), &logins, fix_encoding),
+                account.token.clone(),
+                channels,
+                self.event_tx.clone(),
+                account_rx,
+                broadcast_tx.clone(),
+            ));
+        }
+
+        #[cfg(target_os = "linux")]
+        let dbus = crate::dbus::Dbus::connect().await.ok();
+        #[cfg(target_os = "linux")]
+        if let Some(dbus) = &dbus {
+            tokio::spawn(
+                dbus.clone()
+                    .watch_mentions(self.cfg.clone(), broadcast_tx.subscribe()),
+            );
+        }
+
+        #[cfg(feature = "sound")]
+        if self.cfg.mention_sound.is_some() {
+            tokio::spawn(crate::sound::watch_mentions(
+                self.cfg.clone(),
+                broadcast_tx.subscribe(),
+            ));
+        }
+        #[cfg(not(feature = "sound"))]
+        if self.cfg.mention_sound.is_some() {
+            tracing::warn!("mention_sound is set, but tuige was built without the sound feature");
+        }
+
+        if let Some(dir) = &self.cfg.log_dir {
+            tokio::spawn(ChatLog::new(dir.clone()).run(broadcast_tx.subscribe()));
+        }
+
+        if !self.cfg.auto_responses.is_empty() {
+            tokio::spawn(Self::auto_respond(
+                self.cfg.clone(),
+                broadcast_tx.subscribe(),
+                message_tx.clone(),
+                self.event_tx.clone(),
+            ));
+        }
+
+        {
+            let event_tx = self.event_tx.clone();
+            report_task(&event_tx, "config watcher", TaskState::Running);
+            tokio::spawn(async move {
+                if let Err(e) = Self::watch_config(event_tx.clone()).await {
+                    report_task(
+                        &event_tx,
+                        "config watcher",
+                        TaskState::Failed(e.to_string()),
+                    );
+                }
+            });
+        }
+
+        if let Some(port) = self.cfg.metrics_port {
+            let event_tx = self.event_tx.clone();
+            report_task(&event_tx, "metrics", TaskState::Running);
+            tokio::spawn(async move {
+                if let Err(e) = metrics::serve(port).await {
+                    report_task(&event_tx, "metrics", TaskState::Failed(e.to_string()));
+                }
+            });
+        }
+
+        if let Some(port) = self.cfg.grpc_port {
+            let remote = Remote::new(
+                self.cfg.clone(),
+                self.event_tx.clone(),
+                message_tx.clone(),
+                broadcast_tx,
+            );
+            let event_tx = self.event_tx.clone();
+            report_task(&event_tx, "remote control", TaskState::Running);
+            tokio::spawn(async move {
+                if let Err(e) = remote.serve(port).await {
+                    report_task(
+                        &event_tx,
+                        "remote control",
+                        TaskState::Failed(e.to_string()),
+                    );
+                }
+            });
+        }
+
+        if self.cfg.max_message_age_seconds.is_some() {
+            let event_tx = self.event_tx.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    if event_tx.send(ev::In::PruneMessages).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        for step in self.cfg.startup_order.clone() {
+            match step {
+                // Stream events are optional, chat keeps working without them
+                StartupStep::ValidateToken => {
+                    if self.resolve_ids().await.is_err() {
+                        continue;
+                    }
+                    if let Ok(eventsub) = self.eventsub().await {
+                        #[cfg(target_os = "linux")]
+                        let eventsub = match dbus.clone() {
+                            Some(dbus) => eventsub.with_dbus(dbus),
+                            None => eventsub,
+                        };
+                        let mut eventsub = eventsub;
+                        let event_tx = self.event_tx.clone();
+                        tokio::spawn(async move {
+                            let state = match eventsub.run().await {
+                                Ok(()) => TaskState::Finished,
+                                Err(e) => TaskState::Failed(e.to_string()),
+                            };
+                            report_task(&event_tx, "eventsub", state);
+                        });
+                    }
+                }
+                StartupStep::JoinChannels => {
+                    for channel in &self.cfg.channels {
+                        let _ = message_tx.send(ev::Send::Join(channel.to_string()));
+                    }
+                }
+                StartupStep::FetchGlobalEmotes => self.api().load_emotes(None, None),
+                StartupStep::FetchChannelEmotes => {
+                    let api = self.api();
+                    for id in self.broadcaster_ids.values() {
+                        api.load_emotes(Some(id.clone()), None);
+                    }
+                }
             }
-        });
+        }
+
+        let mut schedule_interval = tokio::time::interval(Duration::from_secs(15 * 60));
+        let mut category_interval = tokio::time::interval(Duration::from_secs(5 * 60));
+        let mut stream_status_interval = tokio::time::interval(Duration::from_secs(60));
 
         loop {
             let term_event = reader.next().fuse();
 
             select! {
+                _ = schedule_interval.tick() => {
+                    self.spawn(|mut api| async move { api.refresh_schedules().await });
+                }
+                _ = stream_status_interval.tick() => {
+                    self.spawn(|mut api| async move { api.refresh_stream_status().await });
+                }
+                _ = category_interval.tick(), if !self.cfg.watch_categories.is_empty() => {
+                    self.spawn(|mut api| async move { api.refresh_category_streams().await });
+                }
                 e = self.handler_rx.recv() => {
                     if let Some(e) = e {
                         match e {
-                            ev::Send::Message(message) => {
-                                let _ = message_tx.send(message);
+                            ev::Send::SearchChannels(query) => {
+                                self.spawn(|mut api| async move { api.search_channels(query).await });
+                            }
+                            ev::Send::FetchDrops => {
+                                self.spawn(|mut api| async move { api.fetch_drops().await });
+                            }
+                            ev::Send::InvalidateCache(channel) => self.spawn(|mut api| async move {
+                                if let Err(e) = api.refresh_emotes(&channel).await {
+                                    let _ = api.event_tx.send(ev::In::Notification(format!(
+                                        "Could not refresh emotes of {channel}: {e}"
+                                    )));
+                                }
+                            }),
+                            ev::Send::FetchCacheStats => self.spawn(|mut api| async move {
+                                if let Err(e) = api.cache.count_disk_usage().await {
+                                    tracing::warn!(error = %e, "could not count the disk cache");
+                                }
+                                let stats = api.cache.stats();
+                                let _ = api.event_tx.send(ev::In::CacheStats(stats));
+                            }),
+                            ev::Send::ExportLog { channel, path, text } => {
+                                let event_tx = self.event_tx.clone();
+                                tokio::spawn(async move {
+                                    let msg = match tokio::fs::write(&path, text).await {
+                                        Ok(()) => {
+                                            format!("Exported {channel} to {}", path.display())
+                                        }
+                                        Err(e) => format!("Could not export {channel}: {e}"),
+                                    };
+                                    let _ = event_tx.send(ev::In::Notification(msg));
+                                });
+                            }
+                            ev::Send::FetchRewards(channel) => {
+                                self.spawn(|mut api| async move { api.fetch_rewards(channel).await });
+                            }
+                            ev::Send::SetRewardEnabled { channel, reward_id, enabled } => {
+                                self.spawn(|mut api| async move {
+                                    if let Err(e) =
+                                        api.set_reward_enabled(&channel, &reward_id, enabled).await
+                                    {
+                                        let _ = api.event_tx.send(ev::In::Message(
+                                            Message::system(
+                                                channel.clone(),
+                                                format!("Could not update reward: {e}"),
+                                            ),
+                                        ));
+                                    }
+                                    api.fetch_rewards(channel).await;
+                                });
+                            }
+                            ev::Send::Ban { channel, username, duration } => {
+                                self.spawn(|mut api| async move {
+                                    if let Err(e) = api.ban(&channel, &username, duration).await {
+                                        let _ = api.event_tx.send(ev::In::Message(
+                                            Message::system(
+                                                channel,
+                                                format!("Could not ban {username}: {e}"),
+                                            ),
+                                        ));
+                                    }
+                                });
+                            }
+                            ev::Send::CreatePoll { channel, title, choices, duration } => {
+                                self.spawn(|mut api| async move {
+                                    let msg = match api
+                                        .create_poll(&channel, &title, &choices, duration)
+                                        .await
+                                    {
+                                        Ok(id) => format!("Started poll {id}"),
+                                        Err(e) => format!("Could not start poll: {e}"),
+                                    };
+                                    let _ = api
+                                        .event_tx
+                                        .send(ev::In::Message(Message::system(channel, msg)));
+                                });
+                            }
+                            ev::Send::Commercial { channel, length } => {
+                                self.spawn(|mut api| async move {
+                                    let msg = match api.start_commercial(&channel, length).await {
+                                        Ok(length) => format!("Ad break starting: {length}s"),
+                                        Err(e) => format!("Could not start ad break: {e}"),
+                                    };
+                                    let _ = api
+                                        .event_tx
+                                        .send(ev::In::Message(Message::system(channel, msg)));
+                                });
+                            }
+                            ev::Send::Vip { channel, username, vip } => {
+                                self.spawn(|mut api| async move {
+                                    let msg = match api.vip(&channel, &username, vip).await {
+                                        Ok(()) if vip => format!("Added {username} as a VIP"),
+                                        Ok(()) => format!("Removed {username} as a VIP"),
+                                        Err(e) => format!(
+                                            "Could not change VIP status of {username}: {e}"
+                                        ),
+                                    };
+                                    let _ = api
+                                        .event_tx
+                                        .send(ev::In::Message(Message::system(channel, msg)));
+                                });
+                            }
+                            e => {
+                                let account_tx = match &e {
+                                    ev::Send::Message(message)
+                                    | ev::Send::Reply { message, .. } => message
+                                        .account
+                                        .as_ref()
+                                        .and_then(|account| account_txs.get(account))
+                                        .cloned(),
+                                    _ => None,
+                                };
+                                let _ = account_tx.as_ref().unwrap_or(&message_tx).send(e);
                             }
                         }
                     }
@@ -105,6 +853,144 @@ impl EventHandler {
         }
     }
 
+    async fn resolve_ids(&mut self) -> eyre::Result<()> {
+        let token = self.cfg.token.to_string();
+
+        let Value::ClientId(client_id) = self.cache.get_client_id(&token).await? else {
+            eyre::bail!("expected client id");
+        };
+        let client_id = client_id.clone();
+
+        // A misspelled channel only loses its own stream features
+        for channel in &self.cfg.channels {
+            let login = channel.trim_start_matches('#');
+            match self.cache.get_user_id(&client_id, login, &token).await {
+                Ok(Value::UserId(id)) => {
+                    self.broadcaster_ids.insert(channel.to_string(), id.clone());
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = self.event_tx.send(ev::In::Message(Message::system(
+                        channel.to_string(),
+                        format!("Could not look up {login}: {e}"),
+                    )));
+                }
+            }
+        }
+
+        self.client_id = Some(client_id);
+        Ok(())
+    }
+
+    async fn eventsub(&mut self) -> eyre::Result<EventSub> {
+        let token = self.cfg.token.to_string();
+        let client_id = self
+            .client_id
+            .clone()
+            .ok_or_eyre("client id not resolved")?;
+
+        let mut broadcasters = Vec::with_capacity(self.broadcaster_ids.len());
+        for (channel, id) in &self.broadcaster_ids {
+            let info = self.cache.get_channel_info(&client_id, id, &token).await?;
+            let _ = self.event_tx.send(ev::In::StreamDelay {
+                channel: channel.clone(),
+                delay: info.delay,
+            });
+            let _ = self.event_tx.send(ev::In::StreamInfoUpdated(StreamInfo {
+                channel: channel.clone(),
+                title: info.title.clone(),
+                category: info.game_name.clone(),
+            }));
+            broadcasters.push(Broadcaster {
+                channel: channel.clone(),
+                id: id.clone(),
+                title: info.title,
+                category: info.game_name,
+            });
+        }
+
+        let mut team_members = HashMap::with_capacity(self.cfg.team_members.len());
+        for login in &self.cfg.team_members {
+            // Team members we can't look up are not watched
+            if let Ok(Value::UserId(id)) = self.cache.get_user_id(&client_id, login, &token).await {
+                team_members.insert(id.clone(), login.clone());
+            }
+        }
+
+        Ok(EventSub::new(
+            self.cache.http().clone(),
+            client_id,
+            token,
+            broadcasters,
+            self.event_tx.clone(),
+        )
+        .with_team_members(team_members))
+    }
+
+    /// Helix requests, made in the background with their own cache
+    fn api(&self) -> Api {
+        Api {
+            event_tx: self.event_tx.clone(),
+            cfg: self.cfg.clone(),
+            cache: self.cache.fork(),
+            client_id: self.client_id.clone(),
+            broadcaster_ids: self.broadcaster_ids.clone(),
+        }
+    }
+
+    /// Runs a request in the background so input isn't held up waiting for it
+    fn spawn<F: Future<Output = ()> + Send + 'static>(&self, request: impl FnOnce(Api) -> F) {
+        tokio::spawn(request(self.api()));
+    }
+
+    /// Answers chat messages found in `auto_responses`, after `autoresponse_delay_ms`
+    async fn auto_respond(
+        cfg: Config,
+        mut messages: broadcast::Receiver<Message>,
+        message_tx: UnboundedSender<ev::Send>,
+        event_tx: UnboundedSender<ev::In>,
+    ) {
+        // When each trigger was last answered, by channel
+        let mut answered = HashMap::<(String, String), Instant>::new();
+        loop {
+            let message = match messages.recv().await {
+                Ok(message) => message,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if message.kind != MessageKind::Chat || message.username == cfg.username {
+                continue;
+            }
+            let trigger = message.msg.trim();
+            let Some(response) = cfg.auto_responses.get(trigger) else {
+                continue;
+            };
+            let key = (message.channel.clone(), trigger.to_string());
+            if answered
+                .get(&key)
+                .is_some_and(|at| at.elapsed() < cfg.autoresponse_cooldown())
+            {
+                continue;
+            }
+            answered.insert(key, Instant::now());
+
+            let response = Message::new(
+                message.channel,
+                cfg.username.to_string(),
+                response.clone(),
+                MessageKind::Chat,
+            );
+            let message_tx = message_tx.clone();
+            let event_tx = event_tx.clone();
+            let delay = cfg.autoresponse_delay();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = message_tx.send(ev::Send::Message(response.clone()));
+                let _ = event_tx.send(ev::In::Message(response));
+            });
+        }
+    }
+
     async fn crossterm_event(
         term_event: Fuse<Next<'_, EventStream>>,
         event_tx: &mut UnboundedSender<ev::In>,
@@ -115,6 +1001,9 @@ impl EventHandler {
                 crossterm::event::Event::Key(k) => {
                     event_tx.send(ev::In::Key(k))?;
                 }
+                crossterm::event::Event::Mouse(m) => {
+                    event_tx.send(ev::In::Mouse(m))?;
+                }
                 crossterm::event::Event::Resize(_, _) => {
                     event_tx.send(ev::In::Redraw)?;
                 }
@@ -124,25 +1013,268 @@ impl EventHandler {
         Ok(())
     }
 
+    /// Asks for a reload whenever the config file changes. The directory is watched since editors
+    /// often replace the file rather than write to it
+    async fn watch_config(event_tx: UnboundedSender<ev::In>) -> eyre::Result<()> {
+        let path = config::path()?;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        use notify::Watcher;
+
+        let mut watcher = notify::recommended_watcher(move |event| {
+            if let Ok(event) = event {
+                let _ = tx.send(event);
+            }
+        })?;
+        watcher.watch(
+            path.parent().ok_or_eyre("config file has no directory")?,
+            notify::RecursiveMode::NonRecursive,
+        )?;
+
+        while let Some(event) = rx.recv().await {
+            let event: notify::Event = event;
+            if !(event.kind.is_modify() || event.kind.is_create()) || !event.paths.contains(&path) {
+                continue;
+            }
+            tokio::time::sleep(CONFIG_RELOAD_DELAY).await;
+            while rx.try_recv().is_ok() {}
+
+            let cfg = config::load(&path)
+                .map(ReloadedConfig)
+                .map_err(|e| e.to_string());
+            event_tx.send(ev::In::ConfigReload(cfg))?;
+        }
+        Ok(())
+    }
+
+    /// Chat connection of an extra account, sending messages tagged with its username.
+    /// Channels only need joining to read them, sending works in any channel
+    async fn run_account(
+        connection: ChatConnection,
+        token: String,
+        mut channels: Vec<String>,
+        mut event_tx: UnboundedSender<ev::In>,
+        mut message_rx: UnboundedReceiver<ev::Send>,
+        broadcast_tx: broadcast::Sender<Message>,
+    ) {
+        let username = connection.account.clone().unwrap_or_default();
+        let task = format!("chat ({username})");
+        let client = tmi::Client::builder()
+            .credentials(tmi::Credentials {
+                login: username.clone(),
+                token: Some(token),
+            })
+            .connect()
+            .await;
+        let mut client = match client {
+            Ok(client) => client,
+            Err(e) => {
+                report_task(&event_tx, &task, TaskState::Failed(e.to_string()));
+                return;
+            }
+        };
+        if !channels.is_empty() {
+            if let Err(e) = client.join_all(&channels).await {
+                report_task(&event_tx, &task, TaskState::Failed(e.to_string()));
+                return;
+            }
+        }
+
+        report_task(&event_tx, &task, TaskState::Running);
+        loop {
+            if let Err(e) = Self::tmi_event(
+                &mut channels,
+                &mut client,
+                &mut event_tx,
+                &mut message_rx,
+                &broadcast_tx,
+                &connection,
+            )
+            .await
+            {
+                report_task(&event_tx, &task, TaskState::Failed(e.to_string()));
+                break;
+            }
+        }
+    }
+
     async fn tmi_event(
-        cfg: &Config,
+        channels: &mut Vec<String>,
         client: &mut tmi::Client,
         event_tx: &mut UnboundedSender<ev::In>,
-        message_rx: &mut UnboundedReceiver<Message>,
+        message_rx: &mut UnboundedReceiver<ev::Send>,
+        broadcast_tx: &broadcast::Sender<Message>,
+        connection: &ChatConnection,
     ) -> eyre::Result<()> {
+        let account = connection.account.as_deref();
         select! {
             msg = client.recv() => {
-                match msg?.as_typed()? {
+                let msg = msg?;
+                let power_up = msg.tag("animation-id").is_some();
+                let raw = msg.raw();
+                match msg.as_typed()? {
+                    tmi::Message::Privmsg(msg)
+                        if connection.is_other_account(msg.sender().login()) => {}
                     tmi::Message::Privmsg(msg) => {
-                        event_tx.send(ev::In::Message(Message {
-                            channel: msg.channel().into(),
-                            username: msg.sender().name().into(),
-                            msg: msg.text().into(),
-                        }))?;
+                        let text = if connection.fix_encoding {
+                            fix_mojibake(msg.text())
+                        } else {
+                            msg.text().into()
+                        };
+                        let message = Message {
+                            id: Some(msg.id().into()),
+                            power_up,
+                            raw: Some(raw.into()),
+                            color: msg.color().and_then(parse_color),
+                            badges: msg
+                                .badges()
+                                .map(|badge| badge.as_badge_data().name().to_string())
+                                .collect(),
+                            account: account.map(String::from),
+                            ..Message::new(
+                                msg.channel().into(),
+                                msg.sender().name().into(),
+                                text,
+                                MessageKind::Chat,
+                            )
+                        };
+                        metrics::message_received(&message.channel);
+                        let _ = broadcast_tx.send(message.clone());
+                        event_tx.send(ev::In::Message(message))?;
+                    }
+                    tmi::Message::UserNotice(notice) => {
+                        let sender = notice.sender().map(|sender| sender.name().to_string());
+                        let kind = match (notice.event(), sender) {
+                            (tmi::Event::SubOrResub(sub), Some(user)) => Some(SystemEventKind::Sub {
+                                user,
+                                months: sub.cumulative_months(),
+                            }),
+                            // Subs from a mystery gift are announced by the mystery gift itself
+                            (tmi::Event::SubGift(gift), gifter)
+                                if msg.tag("msg-param-community-gift-id").is_none() =>
+                            {
+                                Some(SystemEventKind::GiftSub {
+                                    gifter,
+                                    recipient: gift.recipient().name().into(),
+                                })
+                            }
+                            (tmi::Event::SubMysteryGift(gift), gifter) => {
+                                Some(SystemEventKind::MysteryGift {
+                                    gifter,
+                                    count: gift.count(),
+                                })
+                            }
+                            (tmi::Event::AnonSubMysteryGift(gift), _) => {
+                                Some(SystemEventKind::MysteryGift {
+                                    gifter: None,
+                                    count: gift.count(),
+                                })
+                            }
+                            (tmi::Event::Raid(raid), Some(raider)) => Some(SystemEventKind::Raid {
+                                raider,
+                                viewers: raid.viewer_count(),
+                            }),
+                            _ => None,
+                        };
+                        if let Some(kind) = kind {
+                            event_tx.send(ev::In::SystemEvent(SystemEvent {
+                                channel: notice.channel().into(),
+                                kind,
+                            }))?;
+                        }
+
+                        if let (tmi::Event::Raid(raid), Some(raider)) =
+                            (notice.event(), notice.sender())
+                        {
+                            event_tx.send(ev::In::Raid {
+                                channel: notice.channel().into(),
+                                raider: raider.name().into(),
+                                viewers: raid.viewer_count(),
+                            })?;
+                        }
+
+                        let count = match notice.event() {
+                            // Subs from a mystery gift are already counted by the mystery gift itself
+                            tmi::Event::SubGift(_)
+                                if msg.tag("msg-param-community-gift-id").is_none() =>
+                            {
+                                1
+                            }
+                            tmi::Event::SubMysteryGift(gift) => gift.count() as usize,
+                            _ => 0,
+                        };
+
+                        if let Some(gifter) = notice.sender().filter(|_| count > 0) {
+                            event_tx.send(ev::In::Gift {
+                                channel: notice.channel().into(),
+                                gifter: gifter.name().into(),
+                                count,
+                            })?;
+                        }
+
+                        if let (tmi::Event::SubGift(_), Some(gifter)) =
+                            (notice.event(), notice.sender())
+                        {
+                            if msg.tag("msg-param-community-gift-id").is_some() {
+                                event_tx.send(ev::In::CommunityGiftSub {
+                                    channel: notice.channel().into(),
+                                    gifter: gifter.name().into(),
+                                })?;
+                            }
+                        }
+                    }
+                    tmi::Message::ClearMsg(clear) => {
+                        event_tx.send(ev::In::MessageDeleted {
+                            channel: clear.channel().into(),
+                            id: clear.target_message_id().into(),
+                        })?;
+                    }
+                    tmi::Message::ClearChat(clear) => {
+                        let timeout = match clear.action() {
+                            tmi::Action::Clear => None,
+                            tmi::Action::Ban(_) => Some(None),
+                            tmi::Action::TimeOut(timeout) => Some(Some(timeout.duration())),
+                        };
+                        if let (Some(user), Some(timeout)) = (clear.target(), timeout) {
+                            event_tx.send(ev::In::UserCleared {
+                                channel: clear.channel().into(),
+                                user: user.into(),
+                                timeout,
+                            })?;
+                        }
+                    }
+                    tmi::Message::RoomState(state) => {
+                        // Partial updates leave out modes that didn't change
+                        let followers_only = state.followers_only().map(|followers_only| {
+                            match followers_only {
+                                tmi::FollowersOnly::Enabled(duration) => {
+                                    Some(duration.map_or(0, |d| d.as_secs() / 60))
+                                }
+                                tmi::FollowersOnly::Disabled => None,
+                            }
+                        });
+                        event_tx.send(ev::In::RoomState {
+                            channel: state.channel().into(),
+                            slow: state
+                                .slow()
+                                .map(|slow| Some(slow.as_secs()).filter(|secs| *secs > 0)),
+                            sub_only: state.subs_only(),
+                            emote_only: state.emote_only(),
+                            followers_only,
+                        })?;
+                    }
+                    tmi::Message::Whisper(whisper) => {
+                        event_tx.send(ev::In::Whisper {
+                            sender: whisper.sender().name().into(),
+                            text: whisper.text().into(),
+                        })?;
                     }
                     tmi::Message::Reconnect => {
+                        let task = account.map_or("chat".into(), |account| format!("chat ({account})"));
+                        report_task(event_tx, &task, TaskState::Retrying);
+                        metrics::inc(&metrics::RECONNECT_ATTEMPTS);
                         client.reconnect().await?;
-                        client.join_all(&cfg.channels).await?;
+                        client.join_all(&*channels).await?;
+                        report_task(event_tx, &task, TaskState::Running);
                     }
                     tmi::Message::Ping(ping) => {
                         client.pong(&ping).await?;
@@ -150,9 +1282,29 @@ impl EventHandler {
                     _ => {}
                 }
             }
-            msg = message_rx.recv() => {
-                if let Some(msg) = msg {
-                    client.privmsg(&msg.channel, &msg.msg).send().await?;
+            e = message_rx.recv() => {
+                match e {
+                    Some(ev::Send::Message(msg)) => {
+                        client.privmsg(&msg.channel, &msg.msg).send().await?;
+                        let _ = broadcast_tx.send(msg);
+                    }
+                    Some(ev::Send::Reply { message, parent_id }) => {
+                        client
+                            .privmsg(&message.channel, &message.msg)
+                            .reply_to(&parent_id)
+                            .send()
+                            .await?;
+                        let _ = broadcast_tx.send(message);
+                    }
+                    Some(ev::Send::Part(channel)) if channels.contains(&channel) => {
+                        client.send_raw(format!("PART {channel}\r\n").as_str()).await?;
+                        channels.retain(|joined| joined != &channel);
+                    }
+                    Some(ev::Send::Join(channel)) if !channels.contains(&channel) => {
+                        client.join(&channel).await?;
+                        channels.push(channel);
+                    }
+                    _ => {}
                 }
             }
         }
@@ -160,3 +1312,362 @@ impl EventHandler {
         Ok(())
     }
 }
+
+impl Api {
+    /// Downloads global emotes, or a channel's emotes, in the background. They can take a while
+    /// on a cold cache. `done` is shown once they're loaded
+    fn load_emotes(&self, broadcaster_id: Option<String>, done: Option<String>) {
+        let Some(client_id) = self.client_id.clone() else {
+            return;
+        };
+        let mut cache = self.cache.fork();
+        let token = self.cfg.token.to_string();
+        let animated = self.cfg.prefer_animated_emotes;
+        let bttv = self.cfg.bttv;
+        let ffz = self.cfg.ffz;
+        let event_tx = self.event_tx.clone();
+        let task = match &broadcaster_id {
+            Some(id) => {
+                let channel = self
+                    .broadcaster_ids
+                    .iter()
+                    .find_map(|(channel, channel_id)| (channel_id == id).then_some(channel));
+                format!("emotes {}", channel.unwrap_or(id))
+            }
+            None => "emotes (global)".into(),
+        };
+        report_task(&event_tx, &task, TaskState::Running);
+        tokio::spawn(async move {
+            let emotes = match &broadcaster_id {
+                Some(id) => {
+                    cache
+                        .get_channel_emotes(id, client_id, token, animated)
+                        .await
+                }
+                None => cache.get_global_emotes(client_id, token, animated).await,
+            }
+            .map(|value| match value {
+                Value::EmoteSet(emotes) => Some(emotes.clone()),
+                _ => None,
+            });
+            let state = match emotes {
+                Ok(Some(mut emotes)) => {
+                    // Third party emotes are extras, Twitch emotes still load without them
+                    let seventv = match &broadcaster_id {
+                        Some(id) => cache.get_7tv_channel_emotes(id).await,
+                        None => cache.get_7tv_global_emotes().await,
+                    };
+                    merge_emote_set(&mut emotes, Provider::SevenTv, seventv);
+                    if bttv {
+                        let bttv = match &broadcaster_id {
+                            Some(id) => cache.get_bttv_channel_emotes(id).await,
+                            None => cache.get_bttv_global_emotes().await,
+                        };
+                        merge_emote_set(&mut emotes, Provider::Bttv, bttv);
+                    }
+                    if ffz {
+                        let ffz = match &broadcaster_id {
+                            Some(id) => cache.get_ffz_channel_emotes(id).await,
+                            None => cache.get_ffz_global_emotes().await,
+                        };
+                        merge_emote_set(&mut emotes, Provider::Ffz, ffz);
+                    }
+                    let _ = event_tx.send(ev::In::Emotes(emotes));
+                    if let Some(done) = done {
+                        let _ = event_tx.send(ev::In::Notification(done));
+                    }
+                    TaskState::Finished
+                }
+                Ok(None) => TaskState::Failed("expected emote set".into()),
+                Err(e) => TaskState::Failed(e.to_string()),
+            };
+            report_task(&event_tx, &task, state);
+        });
+    }
+
+    async fn refresh_emotes(&mut self, channel: &str) -> eyre::Result<()> {
+        let id = match self.broadcaster_ids.get(channel) {
+            Some(id) => id.clone(),
+            None => self.broadcaster_auth(channel).await?.broadcaster_id,
+        };
+
+        for key in self.cache.channel_emote_set_keys(&id) {
+            self.cache.invalidate(&key).await?;
+        }
+        self.load_emotes(Some(id), Some(format!("Refreshed emotes of {channel}")));
+        Ok(())
+    }
+
+    async fn refresh_schedules(&mut self) {
+        let Some(client_id) = &self.client_id else {
+            return;
+        };
+
+        for (channel, id) in &self.broadcaster_ids {
+            // Channels without a schedule respond with 404
+            let segments = self
+                .cache
+                .get_schedule(id, client_id, &self.cfg.token)
+                .await
+                .unwrap_or_default();
+
+            let _ = self.event_tx.send(ev::In::Schedule {
+                channel: channel.clone(),
+                segments,
+            });
+        }
+    }
+
+    async fn refresh_stream_status(&mut self) {
+        let Some(client_id) = self
+            .client_id
+            .as_ref()
+            .filter(|_| !self.broadcaster_ids.is_empty())
+        else {
+            return;
+        };
+
+        let channels: Vec<_> = self
+            .broadcaster_ids
+            .iter()
+            .map(|(channel, id)| (channel.trim_start_matches('#'), id.as_str()))
+            .collect();
+        let mut statuses = match self
+            .cache
+            .get_stream_statuses(&channels, client_id, &self.cfg.token)
+            .await
+        {
+            Ok(statuses) => {
+                report_task(&self.event_tx, "stream status", TaskState::Running);
+                statuses
+            }
+            Err(e) => {
+                let state = TaskState::Failed(e.to_string());
+                report_task(&self.event_tx, "stream status", state);
+                return;
+            }
+        };
+
+        for channel in self.broadcaster_ids.keys() {
+            let login = channel.trim_start_matches('#').to_lowercase();
+            let _ = self.event_tx.send(ev::In::StreamStatus {
+                channel: channel.clone(),
+                status: statuses.remove(&login),
+            });
+        }
+
+        // Twitch only reports the delay of our own channel, and no event says when it changes
+        let own_channel = self.broadcaster_ids.iter().find(|(channel, _)| {
+            channel
+                .trim_start_matches('#')
+                .eq_ignore_ascii_case(&self.cfg.username)
+        });
+        if let Some((channel, id)) = own_channel {
+            if let Ok(info) = self
+                .cache
+                .get_channel_info(client_id, id, &self.cfg.token)
+                .await
+            {
+                let _ = self.event_tx.send(ev::In::StreamDelay {
+                    channel: channel.clone(),
+                    delay: info.delay,
+                });
+            }
+        }
+    }
+
+    async fn refresh_category_streams(&mut self) {
+        let Some(client_id) = self.client_id.clone() else {
+            return;
+        };
+
+        for category in &self.cfg.watch_categories {
+            if let Ok(streams) = self
+                .cache
+                .get_streams_by_category(category, &client_id, &self.cfg.token)
+                .await
+            {
+                let _ = self.event_tx.send(ev::In::CategoryStreams {
+                    category: category.to_string(),
+                    streams,
+                });
+            }
+        }
+    }
+
+    async fn search_channels(&mut self, query: String) {
+        let Some(client_id) = &self.client_id else {
+            return;
+        };
+
+        if let Ok(channels) = self
+            .cache
+            .search_channels(&query, client_id, &self.cfg.token)
+            .await
+        {
+            let _ = self
+                .event_tx
+                .send(ev::In::ChannelSuggestions { query, channels });
+        }
+    }
+
+    /// Failures are shown in the drops panel, the GQL api rejects most third party client ids
+    async fn fetch_drops(&mut self) {
+        let campaigns = match &self.client_id {
+            Some(client_id) => self
+                .cache
+                .get_active_drops(client_id, &self.cfg.token)
+                .await
+                .map_err(|e| format!("Could not fetch drops: {e}")),
+            None => Err("Could not fetch drops: client id not resolved".into()),
+        };
+        let _ = self.event_tx.send(ev::In::Drops(campaigns));
+    }
+
+    /// Client id, token and broadcaster id for helix requests made in `channel`
+    async fn broadcaster_auth(&mut self, channel: &str) -> eyre::Result<BroadcasterAuth> {
+        let client_id = self
+            .client_id
+            .clone()
+            .ok_or_eyre("client id not resolved")?;
+        let token = self.cfg.token.to_string();
+        let broadcaster_id = self
+            .user_id(&client_id, channel.trim_start_matches('#'), &token)
+            .await?;
+        Ok(BroadcasterAuth {
+            client_id,
+            token,
+            broadcaster_id,
+        })
+    }
+
+    async fn user_id(&mut self, client_id: &str, login: &str, token: &str) -> eyre::Result<String> {
+        let Value::UserId(id) = self.cache.get_user_id(client_id, login, token).await? else {
+            eyre::bail!("expected user id");
+        };
+        Ok(id.clone())
+    }
+
+    async fn ban(
+        &mut self,
+        channel: &str,
+        username: &str,
+        duration: Option<u32>,
+    ) -> eyre::Result<()> {
+        let auth = self.broadcaster_auth(channel).await?;
+        let login = self.cfg.username.to_string();
+        let moderator_id = self.user_id(&auth.client_id, &login, &auth.token).await?;
+        let user_id = self.user_id(&auth.client_id, username, &auth.token).await?;
+
+        self.cache
+            .ban_user(
+                &auth.broadcaster_id,
+                &moderator_id,
+                &user_id,
+                duration,
+                &auth.client_id,
+                &auth.token,
+            )
+            .await
+    }
+
+    async fn fetch_rewards(&mut self, channel: String) {
+        let Ok(auth) = self.broadcaster_auth(&channel).await else {
+            return;
+        };
+
+        if let Ok(rewards) = self
+            .cache
+            .get_channel_rewards(&auth.broadcaster_id, &auth.client_id, &auth.token)
+            .await
+        {
+            let _ = self.event_tx.send(ev::In::Rewards { channel, rewards });
+        }
+    }
+
+    async fn set_reward_enabled(
+        &mut self,
+        channel: &str,
+        reward_id: &str,
+        enabled: bool,
+    ) -> eyre::Result<()> {
+        let auth = self.broadcaster_auth(channel).await?;
+
+        self.cache
+            .set_reward_enabled(
+                &auth.broadcaster_id,
+                reward_id,
+                enabled,
+                &auth.client_id,
+                &auth.token,
+            )
+            .await
+    }
+
+    /// Needs the `channel:edit:commercial` scope
+    async fn start_commercial(&mut self, channel: &str, length: u32) -> eyre::Result<u32> {
+        let auth = self.broadcaster_auth(channel).await?;
+
+        self.cache
+            .start_commercial(&auth.broadcaster_id, length, &auth.client_id, &auth.token)
+            .await
+    }
+
+    async fn create_poll(
+        &mut self,
+        channel: &str,
+        title: &str,
+        choices: &[String],
+        duration: u32,
+    ) -> eyre::Result<String> {
+        let auth = self.broadcaster_auth(channel).await?;
+
+        self.cache
+            .create_poll(
+                &auth.broadcaster_id,
+                title,
+                choices,
+                duration,
+                &auth.client_id,
+                &auth.token,
+            )
+            .await
+    }
+
+    async fn vip(&mut self, channel: &str, username: &str, vip: bool) -> eyre::Result<()> {
+        let auth = self.broadcaster_auth(channel).await?;
+        let user_id = self.user_id(&auth.client_id, username, &auth.token).await?;
+
+        self.cache
+            .set_vip(
+                &auth.broadcaster_id,
+                &user_id,
+                vip,
+                &auth.client_id,
+                &auth.token,
+            )
+            .await
+    }
+}
+
+/// Parses a `#RRGGBB` color tag
+fn parse_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Undoes UTF-8 text that was decoded as Windows-1252 and encoded again, like `Ã©` for `é`
+fn fix_mojibake(text: &str) -> String {
+    if text.is_ascii() {
+        return text.into();
+    }
+
+    let (bytes, _, unmappable) = encoding_rs::WINDOWS_1252.encode(text);
+    if unmappable {
+        return text.into();
+    }
+    encoding_rs::UTF_8
+        .decode_without_bom_handling_and_without_replacement(&bytes)
+        .map_or_else(|| text.into(), |fixed| fixed.into_owned())
+}
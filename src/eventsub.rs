@@ -0,0 +1,535 @@
+use std::{collections::HashMap, time::Duration};
+
+use color_eyre::eyre;
+use eyre::OptionExt;
+use futures::StreamExt;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_tungstenite::tungstenite;
+
+#[cfg(target_os = "linux")]
+use crate::dbus::Dbus;
+use crate::event::{
+    ev, report_task, CharityCampaign, CompletedPrediction, ContributionSize, Message, MessageKind,
+    StreamInfo, TaskState,
+};
+use crate::metrics;
+
+const EVENTSUB_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+const SUBSCRIPTIONS_URL: &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Channel we are subscribed to, along with the last known stream info
+pub struct Broadcaster {
+    pub channel: String,
+    pub id: String,
+    pub title: String,
+    pub category: String,
+}
+
+pub struct EventSub {
+    http: reqwest::Client,
+    client_id: String,
+    token: String,
+    broadcasters: HashMap<String, Broadcaster>,
+    /// User id to login of team members we only watch for going live
+    team_members: HashMap<String, String>,
+    event_tx: UnboundedSender<ev::In>,
+    #[cfg(target_os = "linux")]
+    dbus: Option<Dbus>,
+}
+
+impl EventSub {
+    pub fn new(
+        http: reqwest::Client,
+        client_id: String,
+        token: String,
+        broadcasters: Vec<Broadcaster>,
+        event_tx: UnboundedSender<ev::In>,
+    ) -> Self {
+        Self {
+            http,
+            client_id,
+            token,
+            broadcasters: broadcasters
+                .into_iter()
+                .map(|b| (b.id.clone(), b))
+                .collect(),
+            team_members: HashMap::new(),
+            event_tx,
+            #[cfg(target_os = "linux")]
+            dbus: None,
+        }
+    }
+
+    /// Also report when these team members go live, keyed by user id
+    pub fn with_team_members(mut self, team_members: HashMap<String, String>) -> Self {
+        self.team_members = team_members;
+        self
+    }
+
+    /// Also announce stream events over D-Bus
+    #[cfg(target_os = "linux")]
+    pub fn with_dbus(mut self, dbus: Dbus) -> Self {
+        self.dbus = Some(dbus);
+        self
+    }
+
+    pub async fn run(&mut self) -> eyre::Result<()> {
+        let mut url = EVENTSUB_URL.to_string();
+        let mut session_id: Option<String> = None;
+        // Set by `session_reconnect`, whose new session keeps the subscriptions
+        let mut reconnecting = false;
+        let mut health_interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+
+        loop {
+            let (mut ws, _) = tokio_tungstenite::connect_async(&url).await?;
+
+            loop {
+                let msg = tokio::select! {
+                    msg = ws.next() => msg,
+                    _ = health_interval.tick() => {
+                        if let Some(session_id) = &session_id {
+                            let _ = self.check_health(session_id).await;
+                        }
+                        continue;
+                    }
+                };
+                let Some(msg) = msg else {
+                    // A closed session loses its subscriptions and its reconnect url
+                    url = EVENTSUB_URL.to_string();
+                    reconnecting = false;
+                    break;
+                };
+                let tungstenite::Message::Text(text) = msg? else {
+                    continue;
+                };
+
+                let frame = serde_json::from_str::<response::Frame>(&text)?;
+                match frame.metadata.message_type.as_str() {
+                    "session_welcome" => {
+                        let session = serde_json::from_value::<response::Session>(
+                            frame.payload["session"].clone(),
+                        )?;
+                        // Subscriptions carry over to the new session on reconnect
+                        if !reconnecting {
+                            self.subscribe_all(&session.id).await;
+                        }
+                        reconnecting = false;
+                        report_task(&self.event_tx, "eventsub", TaskState::Running);
+                        session_id = Some(session.id);
+                    }
+                    "session_reconnect" => {
+                        let session = serde_json::from_value::<response::Session>(
+                            frame.payload["session"].clone(),
+                        )?;
+                        url = session
+                            .reconnect_url
+                            .ok_or_eyre("reconnect without reconnect_url")?;
+                        reconnecting = true;
+                        report_task(&self.event_tx, "eventsub", TaskState::Retrying);
+                        metrics::inc(&metrics::RECONNECT_ATTEMPTS);
+                        break;
+                    }
+                    "notification" => {
+                        self.notification(frame).await?;
+                        report_task(&self.event_tx, "eventsub", TaskState::Running);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Reports how many subscriptions aren't `enabled`, on any session. Those on this session are
+    /// subscribed again, older sessions' were already replaced when this one started
+    async fn check_health(&self, session_id: &str) -> eyre::Result<()> {
+        let req = self
+            .http
+            .get(SUBSCRIPTIONS_URL)
+            .bearer_auth(&self.token)
+            .header("Client-Id", &self.client_id)
+            .build()?;
+        let subscriptions = self
+            .http
+            .execute(req)
+            .await?
+            .error_for_status()?
+            .json::<response::Subscriptions>()
+            .await?;
+
+        let mut degraded = 0;
+        for subscription in subscriptions.data {
+            if subscription.status == "enabled" {
+                continue;
+            }
+            degraded += 1;
+
+            let req = self
+                .http
+                .delete(SUBSCRIPTIONS_URL)
+                .query(&[("id", &subscription.id)])
+                .bearer_auth(&self.token)
+                .header("Client-Id", &self.client_id)
+                .build()?;
+            let _ = self.http.execute(req).await;
+            if subscription.transport.session_id.as_deref() != Some(session_id) {
+                continue;
+            }
+            let _ = self
+                .subscribe(
+                    session_id,
+                    &subscription.kind,
+                    &subscription.version,
+                    subscription.condition,
+                )
+                .await;
+        }
+
+        self.event_tx.send(ev::In::EventSubHealth { degraded })?;
+        Ok(())
+    }
+
+    async fn subscribe_all(&self, session_id: &str) {
+        #[allow(unused_mut)]
+        let mut kinds = vec![
+            ("channel.update", "2"),
+            ("channel.charity_campaign.start", "1"),
+            ("channel.charity_campaign.progress", "1"),
+            ("channel.charity_campaign.stop", "1"),
+            ("channel.prediction.end", "1"),
+            ("channel.hype_train.begin", "1"),
+            ("channel.hype_train.progress", "1"),
+        ];
+        #[cfg(target_os = "linux")]
+        if self.dbus.is_some() {
+            kinds.push(("stream.online", "1"));
+        }
+
+        for id in self.broadcasters.keys() {
+            for (kind, version) in &kinds {
+                // Subscriptions the token lacks scopes for are skipped
+                let _ = self
+                    .subscribe(
+                        session_id,
+                        kind,
+                        version,
+                        serde_json::json!({ "broadcaster_user_id": id }),
+                    )
+                    .await;
+            }
+        }
+
+        for id in self.team_members.keys() {
+            let _ = self
+                .subscribe(
+                    session_id,
+                    "stream.online",
+                    "1",
+                    serde_json::json!({ "broadcaster_user_id": id }),
+                )
+                .await;
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        session_id: &str,
+        kind: &str,
+        version: &str,
+        condition: serde_json::Value,
+    ) -> eyre::Result<()> {
+        let req = self
+            .http
+            .post(SUBSCRIPTIONS_URL)
+            .bearer_auth(&self.token)
+            .header("Client-Id", &self.client_id)
+            .json(&request::Subscription {
+                kind,
+                version,
+                condition,
+                transport: request::Transport {
+                    method: "websocket",
+                    session_id,
+                },
+            })
+            .build()?;
+
+        self.http.execute(req).await?.error_for_status()?;
+        Ok(())
+    }
+
+    async fn notification(&mut self, frame: response::Frame) -> eyre::Result<()> {
+        match frame.metadata.subscription_type.as_deref() {
+            Some("channel.update") => {
+                let event = serde_json::from_value::<response::ChannelUpdate>(
+                    frame.payload["event"].clone(),
+                )?;
+
+                let Some(broadcaster) = self.broadcasters.get_mut(&event.broadcaster_user_id)
+                else {
+                    return Ok(());
+                };
+
+                let title = (broadcaster.title != event.title).then(|| event.title.clone());
+                let category = (broadcaster.category != event.category_name)
+                    .then(|| event.category_name.clone());
+
+                broadcaster.title = event.title;
+                broadcaster.category = event.category_name;
+
+                self.event_tx.send(ev::In::StreamInfoUpdated(StreamInfo {
+                    channel: broadcaster.channel.clone(),
+                    title: broadcaster.title.clone(),
+                    category: broadcaster.category.clone(),
+                }))?;
+
+                if title.is_some() || category.is_some() {
+                    self.event_tx.send(ev::In::ChannelUpdate {
+                        channel: broadcaster.channel.clone(),
+                        title,
+                        category,
+                    })?;
+                }
+            }
+            Some("channel.charity_campaign.start" | "channel.charity_campaign.progress") => {
+                let event = serde_json::from_value::<response::CharityCampaign>(
+                    frame.payload["event"].clone(),
+                )?;
+
+                if let Some(broadcaster) = self.broadcasters.get(&event.broadcaster_user_id) {
+                    self.event_tx.send(ev::In::Charity {
+                        channel: broadcaster.channel.clone(),
+                        campaign: Some(CharityCampaign {
+                            charity: event.charity_name,
+                            current: event.current_amount.value(),
+                            target: event.target_amount.value(),
+                            currency: event.target_amount.currency,
+                        }),
+                    })?;
+                }
+            }
+            Some("channel.charity_campaign.stop") => {
+                let event = serde_json::from_value::<response::CharityCampaign>(
+                    frame.payload["event"].clone(),
+                )?;
+
+                if let Some(broadcaster) = self.broadcasters.get(&event.broadcaster_user_id) {
+                    self.event_tx.send(ev::In::Charity {
+                        channel: broadcaster.channel.clone(),
+                        campaign: None,
+                    })?;
+                }
+            }
+            Some("channel.prediction.end") => {
+                let event = serde_json::from_value::<response::PredictionEnd>(
+                    frame.payload["event"].clone(),
+                )?;
+
+                if let Some(broadcaster) = self.broadcasters.get(&event.broadcaster_user_id) {
+                    let winner = event
+                        .outcomes
+                        .iter()
+                        .find(|outcome| event.winning_outcome_id.as_ref() == Some(&outcome.id));
+                    let predictors = event
+                        .outcomes
+                        .iter()
+                        .flat_map(|outcome| &outcome.top_predictors)
+                        .map(|p| {
+                            (
+                                p.user_login.clone(),
+                                p.channel_points_used,
+                                p.channel_points_won.unwrap_or_default(),
+                            )
+                        })
+                        .collect();
+
+                    self.event_tx
+                        .send(ev::In::PredictionEnd(CompletedPrediction {
+                            channel: broadcaster.channel.clone(),
+                            title: event.title,
+                            winner: winner.map(|outcome| outcome.title.clone()),
+                            predictors,
+                        }))?;
+                }
+            }
+            // Every contribution is reported as the latest one in a progress event
+            Some("channel.hype_train.begin" | "channel.hype_train.progress") => {
+                let event = serde_json::from_value::<response::HypeTrainProgress>(
+                    frame.payload["event"].clone(),
+                )?;
+
+                if let Some(broadcaster) = self.broadcasters.get(&event.broadcaster_user_id) {
+                    let contribution = event.last_contribution;
+                    let amount = match contribution.kind.as_str() {
+                        "bits" => format!("{} bits", contribution.total),
+                        "subscription" => format!("subs worth {} points", contribution.total),
+                        _ => format!("{} points", contribution.total),
+                    };
+                    self.event_tx.send(ev::In::Message(Message::new(
+                        broadcaster.channel.clone(),
+                        "[hype train]".into(),
+                        format!(
+                            "{} contributed {amount} to the hype train!",
+                            contribution.user_name
+                        ),
+                        MessageKind::HypeTrain(ContributionSize::from_total(contribution.total)),
+                    )))?;
+                }
+            }
+            Some("stream.online") => {
+                let event = serde_json::from_value::<response::StreamOnline>(
+                    frame.payload["event"].clone(),
+                )?;
+
+                if let Some(login) = self.team_members.get(&event.broadcaster_user_id) {
+                    self.event_tx.send(ev::In::TeamMemberLive {
+                        login: login.clone(),
+                    })?;
+                }
+
+                #[cfg(target_os = "linux")]
+                if let (Some(dbus), Some(broadcaster)) = (
+                    &self.dbus,
+                    self.broadcasters.get(&event.broadcaster_user_id),
+                ) {
+                    dbus.stream_live(&broadcaster.channel, &broadcaster.title)
+                        .await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+mod request {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct Subscription<'a> {
+        #[serde(rename = "type")]
+        pub kind: &'a str,
+        pub version: &'a str,
+        pub condition: serde_json::Value,
+        pub transport: Transport<'a>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Transport<'a> {
+        pub method: &'a str,
+        pub session_id: &'a str,
+    }
+}
+
+mod response {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Frame {
+        pub metadata: Metadata,
+        pub payload: serde_json::Value,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Metadata {
+        pub message_type: String,
+        pub subscription_type: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Session {
+        pub id: String,
+        pub reconnect_url: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Subscriptions {
+        pub data: Vec<Subscription>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Subscription {
+        pub id: String,
+        pub status: String,
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub version: String,
+        pub condition: serde_json::Value,
+        pub transport: Transport,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Transport {
+        pub session_id: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct StreamOnline {
+        pub broadcaster_user_id: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct CharityCampaign {
+        pub broadcaster_user_id: String,
+        pub charity_name: String,
+        pub current_amount: Amount,
+        pub target_amount: Amount,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Amount {
+        pub value: i64,
+        pub decimal_places: i32,
+        pub currency: String,
+    }
+
+    impl Amount {
+        pub fn value(&self) -> f64 {
+            self.value as f64 / 10f64.powi(self.decimal_places)
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct PredictionEnd {
+        pub broadcaster_user_id: String,
+        pub title: String,
+        pub winning_outcome_id: Option<String>,
+        pub outcomes: Vec<PredictionOutcome>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct PredictionOutcome {
+        pub id: String,
+        pub title: String,
+        #[serde(default)]
+        pub top_predictors: Vec<Predictor>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Predictor {
+        pub user_login: String,
+        pub channel_points_used: u64,
+        pub channel_points_won: Option<u64>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct HypeTrainProgress {
+        pub broadcaster_user_id: String,
+        pub last_contribution: Contribution,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Contribution {
+        pub user_name: String,
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub total: u64,
+    }
+
+    #[derive(Deserialize)]
+    pub struct ChannelUpdate {
+        pub broadcaster_user_id: String,
+        pub title: String,
+        pub category_name: String,
+    }
+}
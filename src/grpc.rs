@@ -0,0 +1,110 @@
+use std::{net::SocketAddr, pin::Pin};
+
+use color_eyre::eyre;
+use tokio::sync::{broadcast, mpsc::UnboundedSender};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    config::Config,
+    event::{ev, Message, MessageKind},
+};
+
+mod proto {
+    tonic::include_proto!("tuige");
+}
+
+/// gRPC remote control service, see `proto/tuige.proto`
+pub struct Remote {
+    cfg: Config,
+    event_tx: UnboundedSender<ev::In>,
+    message_tx: UnboundedSender<ev::Send>,
+    messages: broadcast::Sender<Message>,
+}
+
+impl Remote {
+    pub fn new(
+        cfg: Config,
+        event_tx: UnboundedSender<ev::In>,
+        message_tx: UnboundedSender<ev::Send>,
+        messages: broadcast::Sender<Message>,
+    ) -> Self {
+        Self {
+            cfg,
+            event_tx,
+            message_tx,
+            messages,
+        }
+    }
+
+    pub async fn serve(self, port: u16) -> eyre::Result<()> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        tonic::transport::Server::builder()
+            .add_service(proto::remote_server::RemoteServer::new(self))
+            .serve(addr)
+            .await?;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl proto::remote_server::Remote for Remote {
+    async fn send_message(
+        &self,
+        request: Request<proto::SendRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let request = request.into_inner();
+        if !self.cfg.channels.iter().any(|c| c == &request.channel) {
+            return Err(Status::not_found("channel is not joined"));
+        }
+
+        let message = Message::new(
+            request.channel,
+            self.cfg.username.to_string(),
+            request.message,
+            MessageKind::Chat,
+        );
+
+        self.message_tx
+            .send(ev::Send::Message(message.clone()))
+            .map_err(|_| Status::unavailable("chat connection is closed"))?;
+        let _ = self.event_tx.send(ev::In::Message(message));
+
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn list_channels(
+        &self,
+        _: Request<proto::Empty>,
+    ) -> Result<Response<proto::ChannelList>, Status> {
+        Ok(Response::new(proto::ChannelList {
+            channels: self.cfg.channels.iter().map(|c| c.to_string()).collect(),
+        }))
+    }
+
+    type StreamMessagesStream =
+        Pin<Box<dyn Stream<Item = Result<proto::ChatMessage, Status>> + Send>>;
+
+    async fn stream_messages(
+        &self,
+        request: Request<proto::ChannelFilter>,
+    ) -> Result<Response<Self::StreamMessagesStream>, Status> {
+        let channels = request.into_inner().channels;
+        let stream = BroadcastStream::new(self.messages.subscribe())
+            .filter_map(move |message| {
+                // Lagging receivers skip the messages they missed
+                let message = message.ok()?;
+                if !channels.is_empty() && !channels.contains(&message.channel) {
+                    return None;
+                }
+                Some(proto::ChatMessage {
+                    channel: message.channel,
+                    username: message.username,
+                    message: message.msg,
+                })
+            })
+            .map(Ok);
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
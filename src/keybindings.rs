@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use color_eyre::eyre::{self, bail, eyre};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Something a key can be bound to under `[keybindings]`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    InsertMode,
+    CommandMode,
+    NextTab,
+    PrevTab,
+    ScrollOlder,
+    ScrollNewer,
+    PageUp,
+    PageDown,
+    ScrollToNewest,
+    Reply,
+    DebugView,
+    Filter,
+    Search,
+    Rewards,
+    Schedule,
+    Leaderboard,
+    Streams,
+    EmoteOnly,
+    Tasks,
+    Predictions,
+    Drops,
+    NextAccount,
+    EmotePicker,
+    ChannelList,
+    UserList,
+    SplitView,
+    SwitchPane,
+}
+
+/// Action names as written in the config, with their default keys
+const ACTIONS: &[(&str, Action, &[&str])] = &[
+    ("quit", Action::Quit, &["q"]),
+    ("insert_mode", Action::InsertMode, &["i"]),
+    ("command_mode", Action::CommandMode, &[":"]),
+    ("next_tab", Action::NextTab, &["tab", "j"]),
+    ("prev_tab", Action::PrevTab, &["shift+tab", "k"]),
+    ("scroll_older", Action::ScrollOlder, &["h"]),
+    ("scroll_newer", Action::ScrollNewer, &["l"]),
+    ("page_up", Action::PageUp, &["pageup"]),
+    ("page_down", Action::PageDown, &["pagedown"]),
+    ("scroll_to_newest", Action::ScrollToNewest, &["G"]),
+    ("reply", Action::Reply, &["r"]),
+    ("debug_view", Action::DebugView, &["D"]),
+    ("filter", Action::Filter, &["/"]),
+    ("search", Action::Search, &["ctrl+f"]),
+    ("rewards", Action::Rewards, &["ctrl+r"]),
+    ("schedule", Action::Schedule, &["ctrl+shift+l"]),
+    ("leaderboard", Action::Leaderboard, &["ctrl+g"]),
+    ("streams", Action::Streams, &["ctrl+b"]),
+    ("emote_only", Action::EmoteOnly, &["ctrl+o"]),
+    ("tasks", Action::Tasks, &["ctrl+d"]),
+    ("predictions", Action::Predictions, &["alt+p"]),
+    ("drops", Action::Drops, &["alt+d"]),
+    ("next_account", Action::NextAccount, &["alt+a"]),
+    ("emote_picker", Action::EmotePicker, &["ctrl+e"]),
+    ("channel_list", Action::ChannelList, &["alt+b"]),
+    ("user_list", Action::UserList, &["ctrl+u"]),
+    ("split_view", Action::SplitView, &["ctrl+s"]),
+    ("switch_pane", Action::SwitchPane, &["alt+w"]),
+];
+
+/// Key combination, shift is folded into the character for letters
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ActionKey {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl ActionKey {
+    fn new(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Keys with ctrl or alt work while typing, the rest only in normal mode
+    pub fn works_while_typing(&self) -> bool {
+        self.modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl From<KeyEvent> for ActionKey {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+/// Parses keys like `q`, `G`, `ctrl+q`, `alt+l` or `shift+tab`
+fn parse_key(text: &str) -> eyre::Result<ActionKey> {
+    let mut parts = text.split('+').collect::<Vec<_>>();
+    // `+` itself is a key
+    if text.ends_with("++") || text == "+" {
+        parts.pop();
+        *parts.last_mut().unwrap() = "+";
+    }
+    let (key, mods) = parts.split_last().ok_or_else(|| eyre!("empty key"))?;
+
+    let mut modifiers = KeyModifiers::NONE;
+    for modifier in mods {
+        modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => bail!("unknown modifier `{modifier}` in `{text}`"),
+        };
+    }
+
+    let mut chars = key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match key.to_ascii_lowercase().as_str() {
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            f if f.starts_with('f') => KeyCode::F(
+                f[1..]
+                    .parse()
+                    .map_err(|_| eyre!("unknown key `{key}` in `{text}`"))?,
+            ),
+            _ => bail!("unknown key `{key}` in `{text}`"),
+        },
+    };
+    Ok(ActionKey::new(code, modifiers))
+}
+
+/// Default keys with the `[keybindings]` overrides applied, an overridden action loses its
+/// default keys. Fails when an override takes a key another action is still bound to
+pub fn keymap(overrides: &HashMap<String, String>) -> eyre::Result<HashMap<ActionKey, Action>> {
+    for name in overrides.keys() {
+        if !ACTIONS.iter().any(|(action, ..)| action == name) {
+            let known = ACTIONS
+                .iter()
+                .map(|(action, ..)| *action)
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!("unknown action `{name}` in [keybindings], expected one of: {known}");
+        }
+    }
+
+    let mut keymap = HashMap::new();
+    for (name, action, keys) in ACTIONS {
+        if !overrides.contains_key(*name) {
+            for key in *keys {
+                keymap.insert(parse_key(key)?, *action);
+            }
+        }
+    }
+    for (name, key) in overrides {
+        let (_, action, _) = ACTIONS.iter().find(|(action, ..)| action == name).unwrap();
+        let parsed = parse_key(key).map_err(|e| eyre!("[keybindings] {name}: {e}"))?;
+        if let Some(other) = keymap.insert(parsed, *action) {
+            let (other, ..) = ACTIONS
+                .iter()
+                .find(|(_, action, _)| *action == other)
+                .unwrap();
+            bail!("[keybindings] {name}: `{key}` is already bound to `{other}`");
+        }
+    }
+    Ok(keymap)
+}
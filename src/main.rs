@@ -2,9 +2,23 @@ use color_eyre::eyre;
 use eyre::OptionExt;
 use tui::Tui;
 
+mod chat_log;
 mod config;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod emoji_map;
+mod emote;
+mod emote_tokenizer;
 mod event;
+mod eventsub;
+mod grpc;
+mod keybindings;
+mod metrics;
+mod persist;
 mod request;
+#[cfg(feature = "sound")]
+mod sound;
+mod theme;
 mod tui;
 
 #[tokio::main]
@@ -14,5 +28,12 @@ async fn main() -> eyre::Result<()> {
         .ok_or_eyre("unable to find cache directory")?
         .join("tuige");
 
+    // The terminal belongs to the tui, so logs go to a file
+    std::fs::create_dir_all(&cache_dir)?;
+    let log_file = std::fs::File::create(cache_dir.join("tuige.log"))?;
+    tracing_subscriber::fmt()
+        .with_writer(std::sync::Mutex::new(log_file))
+        .init();
+
     Tui.run(cfg, cache_dir.to_str().unwrap().into()).await
 }
@@ -0,0 +1,60 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use axum::{routing::get, Router};
+use color_eyre::eyre;
+
+pub static EMOTE_CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+pub static EMOTE_CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+/// Chat and eventsub reconnects requested by Twitch
+pub static RECONNECT_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+static MESSAGES_RECEIVED: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+pub fn inc(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn message_received(channel: &str) {
+    let mut messages = MESSAGES_RECEIVED.lock().unwrap();
+    match messages.get_mut(channel) {
+        Some(count) => *count += 1,
+        None => {
+            messages.insert(channel.into(), 1);
+        }
+    }
+}
+
+/// Serves the counters in the Prometheus text format at `/metrics` on localhost
+pub async fn serve(port: u16) -> eyre::Result<()> {
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port))).await?;
+    axum::serve(listener, Router::new().route("/metrics", get(render))).await?;
+    Ok(())
+}
+
+async fn render() -> String {
+    let mut out = String::new();
+    out.push_str("# TYPE tuige_messages_received_total counter\n");
+    for (channel, count) in MESSAGES_RECEIVED.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "tuige_messages_received_total{{channel=\"{channel}\"}} {count}"
+        );
+    }
+
+    for (name, counter) in [
+        ("tuige_emote_cache_hits_total", &EMOTE_CACHE_HITS),
+        ("tuige_emote_cache_misses_total", &EMOTE_CACHE_MISSES),
+        ("tuige_reconnect_attempts_total", &RECONNECT_ATTEMPTS),
+    ] {
+        let _ = writeln!(out, "# TYPE {name} counter");
+        let _ = writeln!(out, "{name} {}", counter.load(Ordering::Relaxed));
+    }
+    out
+}
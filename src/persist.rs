@@ -0,0 +1,21 @@
+use std::path::Path;
+
+use color_eyre::eyre;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Reads a json file, missing or malformed files are treated as empty
+pub fn load<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save<T: Serialize>(path: impl AsRef<Path>, value: &T) -> eyre::Result<()> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string(value)?)?;
+    Ok(())
+}
@@ -1,42 +1,146 @@
-use color_eyre::eyre::{self};
+use chrono::{DateTime, Local, TimeDelta};
+use color_eyre::eyre::{self, OptionExt};
 use futures::StreamExt;
-use image::DynamicImage;
+use image::{AnimationDecoder, DynamicImage};
 use lru::LruCache;
 use rkyv::{with::CopyOptimize, Archive, Deserialize, Serialize};
-use std::{io::Cursor, num::NonZero};
-use triomphe::Arc;
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    num::NonZero,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::{ConfigData, EmoteScale, Provider},
+    metrics,
+};
+
+/// Bodies logged for responses that fail to deserialize are cut off after this many bytes
+const LOGGED_BODY_LEN: usize = 1024;
+
+/// Global emotes change rarely, but a session shouldn't keep them forever
+const GLOBAL_EMOTE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+/// 7TV sets are edited by channel editors at any time
+const SEVENTV_EMOTE_TTL: Duration = Duration::from_secs(60 * 60);
+/// BTTV channel emotes change as often as 7TV ones
+const BTTV_EMOTE_TTL: Duration = Duration::from_secs(60 * 60);
+const FFZ_EMOTE_TTL: Duration = Duration::from_secs(60 * 60);
+
+trait ResponseExt {
+    /// Like [`reqwest::Response::json`], but logs the body when it doesn't match `T` so api
+    /// changes can be diagnosed
+    async fn json_or_warn<T: serde::de::DeserializeOwned>(self) -> eyre::Result<T>;
+}
+
+impl ResponseExt for reqwest::Response {
+    async fn json_or_warn<T: serde::de::DeserializeOwned>(self) -> eyre::Result<T> {
+        let url = self.url().clone();
+        let body = self.text().await?;
+        serde_json::from_str(&body).map_err(|e| {
+            let mut end = body.len().min(LOGGED_BODY_LEN);
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            tracing::warn!(%url, error = %e, body = &body[..end], "unexpected api response");
+            e.into()
+        })
+    }
+}
 
 pub struct Cache {
     http: reqwest::Client,
     disk_cache_dir: String,
-    lru: lru::LruCache<String, Value>,
+    /// Values and when they expire, `None` never does
+    lru: lru::LruCache<String, (Option<SystemTime>, Value)>,
+    /// Shared with every [`Cache::fork`], so lookups made in background tasks are counted too
+    stats: Arc<Mutex<CacheStats>>,
+    /// `lru.len()` as last added to `stats.lru_size`
+    counted_lru_len: usize,
+    emote_download_concurrency: usize,
+    emote_scale: EmoteScale,
+    /// Recent channel searches, kept briefly to avoid hammering the api
+    channel_search: LruCache<String, (Instant, Vec<String>)>,
+    /// How long emotes from each provider stay on disk, forever when missing
+    emote_ttl: HashMap<Provider, Duration>,
 }
 
 impl Cache {
-    pub fn new(disk_cache_dir: String) -> Self {
-        Self {
-            http: reqwest::Client::new(),
+    pub fn new(disk_cache_dir: String, cfg: &ConfigData) -> eyre::Result<Self> {
+        let mut http = reqwest::Client::builder();
+        if let Some(max_idle) = cfg.http_pool_max_idle_per_host {
+            http = http.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(proxy) = &cfg.proxy {
+            http = http.proxy(proxy.to_reqwest()?);
+        }
+
+        Ok(Self {
+            http: http.build()?,
             disk_cache_dir,
             lru: LruCache::new(NonZero::new(100).unwrap()),
+            stats: Arc::default(),
+            counted_lru_len: 0,
+            emote_download_concurrency: cfg.emote_download_concurrency(),
+            emote_scale: cfg.emote_scale,
+            channel_search: LruCache::new(NonZero::new(20).unwrap()),
+            emote_ttl: Provider::ALL
+                .into_iter()
+                .filter_map(|provider| Some((provider, cfg.emote_ttl(provider)?)))
+                .collect(),
+        })
+    }
+
+    /// A cache with its own in-memory entries, sharing the http client, disk cache and stats
+    pub fn fork(&self) -> Self {
+        Self {
+            http: self.http.clone(),
+            disk_cache_dir: self.disk_cache_dir.clone(),
+            lru: LruCache::new(self.lru.cap()),
+            stats: self.stats.clone(),
+            counted_lru_len: 0,
+            emote_download_concurrency: self.emote_download_concurrency,
+            emote_scale: self.emote_scale,
+            channel_search: LruCache::new(self.channel_search.cap()),
+            emote_ttl: self.emote_ttl.clone(),
         }
     }
 
+    fn update_lru_size(&mut self) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.lru_size = stats.lru_size + self.lru.len() - self.counted_lru_len;
+        self.counted_lru_len = self.lru.len();
+    }
+
     async fn read_cache(
         &mut self,
         use_disk_cache: bool,
         key: &str,
     ) -> eyre::Result<Option<&Value>> {
+        // Long sessions would otherwise keep using the in-memory copy past its expiry
+        if self
+            .lru
+            .peek(key)
+            .is_some_and(|(expires_at, _)| is_expired(*expires_at))
+        {
+            self.lru.pop(key);
+        }
         if self.lru.contains(key) {
-            return Ok(Some(self.lru.get(key).unwrap()));
+            self.stats.lock().unwrap().lru_hits += 1;
+            return Ok(Some(&self.lru.get(key).unwrap().1));
         }
+        self.stats.lock().unwrap().lru_misses += 1;
 
         if use_disk_cache {
-            if let Ok(val) = Self::read_disk_cache_bytes::<Vec<u8>>(&self.disk_cache_dir, key).await
+            if let Some((expires_at, val)) = self
+                .read_disk_value(key)
+                .await
+                .and_then(|(expires_at, val)| Some((expires_at, Value::try_from(val).ok()?)))
             {
-                if let Ok(val) = rkyv::from_bytes::<RawCacheValue>(&val[..]) {
-                    self.lru.put(key.into(), val.into());
-                    return Ok(Some(self.lru.get(key).unwrap()));
-                }
+                self.lru.put(key.into(), (expires_at, val));
+                self.update_lru_size();
+                return Ok(Some(&self.lru.get(key).unwrap().1));
             }
         }
 
@@ -48,21 +152,88 @@ impl Cache {
         use_disk_cache: bool,
         key: &str,
         val: RawCacheValue,
+        ttl: Option<Duration>,
     ) -> eyre::Result<&Value> {
+        let expires_at = ttl.map(|ttl| SystemTime::now() + ttl);
         if use_disk_cache {
-            self.write_disk_cache(key, &val).await?;
+            self.write_disk_cache(key, val.clone(), expires_at).await?;
         }
 
-        self.lru.put(key.into(), val.into());
-        Ok(self.lru.get(key).unwrap())
+        self.lru.put(key.into(), (expires_at, val.try_into()?));
+        self.update_lru_size();
+        Ok(&self.lru.get(key).unwrap().1)
     }
 
-    async fn write_disk_cache(&mut self, key: &str, val: &RawCacheValue) -> eyre::Result<()> {
-        let data = rkyv::to_bytes::<RawCacheValue, 1024>(val).unwrap();
+    async fn write_disk_cache(
+        &mut self,
+        key: &str,
+        value: RawCacheValue,
+        expires_at: Option<SystemTime>,
+    ) -> eyre::Result<()> {
+        let entry = TimestampedRawCacheValue {
+            expires_at: expires_at.map(|expires_at| {
+                expires_at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64
+            }),
+            value,
+        };
+        let data = rkyv::to_bytes::<TimestampedRawCacheValue, 1024>(&entry).unwrap();
         Self::write_disk_cache_bytes(&self.disk_cache_dir, key, data).await?;
         Ok(())
     }
 
+    /// Decodes an unexpired value straight from disk, bypassing the in-memory cache
+    async fn read_disk_value(&mut self, key: &str) -> Option<(Option<SystemTime>, RawCacheValue)> {
+        let entry = Self::read_disk_cache_bytes::<Vec<u8>>(&self.disk_cache_dir, key)
+            .await
+            .ok()
+            .and_then(|bytes| rkyv::from_bytes::<TimestampedRawCacheValue>(&bytes[..]).ok())
+            .map(|entry| {
+                let expires_at = entry
+                    .expires_at
+                    .map(|millis| UNIX_EPOCH + Duration::from_millis(millis));
+                (expires_at, entry.value)
+            })
+            .filter(|(expires_at, _)| !is_expired(*expires_at));
+        if entry.is_some() {
+            self.stats.lock().unwrap().disk_hits += 1;
+        } else {
+            self.stats.lock().unwrap().disk_misses += 1;
+        }
+        entry
+    }
+
+    /// Drops `key` from memory and disk so the next lookup fetches it again
+    pub async fn invalidate(&mut self, key: &str) -> eyre::Result<()> {
+        self.lru.pop(key);
+        self.update_lru_size();
+        cacache::remove(&self.disk_cache_dir, key).await?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Counts the entries in the disk cache index and their total size for `stats`
+    pub async fn count_disk_usage(&mut self) -> eyre::Result<()> {
+        let dir = self.disk_cache_dir.clone();
+        let (count, bytes) = tokio::task::spawn_blocking(move || {
+            cacache::list_sync(dir)
+                .flatten()
+                .fold((0, 0), |(count, bytes), entry| {
+                    (count + 1, bytes + entry.size as u64)
+                })
+        })
+        .await?;
+        let mut stats = self.stats.lock().unwrap();
+        stats.disk_entry_count = count;
+        stats.disk_bytes = bytes;
+        Ok(())
+    }
+
     async fn read_disk_cache_bytes<T: From<Vec<u8>>>(dir: &str, key: &str) -> eyre::Result<T> {
         let value = cacache::read(&dir, key).await?;
         Ok(value.into())
@@ -101,7 +272,12 @@ impl Cache {
             let value = callback(self);
 
             (*ptr)
-                .write_cache(descriptor.use_disk_cache, descriptor.key, value.await?)
+                .write_cache(
+                    descriptor.use_disk_cache,
+                    descriptor.key,
+                    value.await?,
+                    descriptor.ttl,
+                )
                 .await
         }
     }
@@ -116,6 +292,7 @@ impl Cache {
             CacheValueDescriptor {
                 use_disk_cache: true,
                 key: &format!("{url}/{hashed_token}"),
+                ttl: None,
             },
             |cache| async move {
                 let req = cache.http.get(url).bearer_auth(token).build()?;
@@ -124,7 +301,7 @@ impl Cache {
                     .http
                     .execute(req)
                     .await?
-                    .json::<response::twitch::Validate>()
+                    .json_or_warn::<response::twitch::Validate>()
                     .await?;
 
                 Ok(RawCacheValue::ClientId(resp.client_id))
@@ -139,17 +316,19 @@ impl Cache {
         username: &str,
         token: &str,
     ) -> eyre::Result<&Value> {
-        let url = &format!("https://api.twitch.tv/helix/users?login={username}");
+        let key = &format!("https://api.twitch.tv/helix/users?login={username}");
 
         self.cache(
             CacheValueDescriptor {
                 use_disk_cache: true,
-                key: url,
+                key,
+                ttl: None,
             },
             |cache| async move {
                 let req = cache
                     .http
-                    .get(url)
+                    .get("https://api.twitch.tv/helix/users")
+                    .query(&[("login", username)])
                     .bearer_auth(token)
                     .header("Client-Id", client_id)
                     .build()?;
@@ -158,88 +337,997 @@ impl Cache {
                     .http
                     .execute(req)
                     .await?
-                    .json::<response::twitch::User>()
+                    .json_or_warn::<response::twitch::User>()
                     .await?;
 
-                Ok(RawCacheValue::UserId(resp.data.first().unwrap().id.clone()))
+                let user = resp
+                    .data
+                    .first()
+                    .ok_or_else(|| eyre::eyre!("unknown user `{username}`"))?;
+                Ok(RawCacheValue::UserId(user.id.clone()))
             },
         )
         .await
     }
 
-    pub async fn get_global_emotes(
+    pub async fn get_game_id(
         &mut self,
-        client_id: String,
-        token: String,
+        name: &str,
+        client_id: &str,
+        token: &str,
     ) -> eyre::Result<&Value> {
-        let client_id = Arc::new(client_id);
-        let token = Arc::new(token);
-        let url = "https://api.twitch.tv/helix/chat/emotes/global";
+        let url = &format!("https://api.twitch.tv/helix/games?name={name}");
+
         self.cache(
             CacheValueDescriptor {
                 use_disk_cache: true,
                 key: url,
+                ttl: None,
             },
             |cache| async move {
                 let req = cache
                     .http
                     .get(url)
-                    .bearer_auth(token.as_ref())
-                    .header("Client-Id", client_id.as_ref())
+                    .bearer_auth(token)
+                    .header("Client-Id", client_id)
                     .build()?;
 
                 let resp = cache
                     .http
                     .execute(req)
                     .await?
-                    .json::<response::twitch::GlobalEmotes>()
+                    .json_or_warn::<response::twitch::Games>()
                     .await?;
 
-                let emote_count = resp.data.len();
-                let http = cache.http.clone();
-                let set = futures::stream::iter(resp.data)
-                    .map(|emote| {
-                        let http = http.clone();
-                        let token = token.clone();
-                        tokio::spawn(async move {
-                            let image_1x = emote.images.get("url_1x").unwrap();
+                let game = resp.data.into_iter().next().ok_or_eyre("game not found")?;
+                Ok(RawCacheValue::GameId(game.id))
+            },
+        )
+        .await
+    }
 
-                            // download emote
-                            let req = http.get(image_1x).bearer_auth(token).build().unwrap();
+    /// Fetches the top 20 live streams in a category, the streams are never cached
+    pub async fn get_streams_by_category(
+        &mut self,
+        category_name: &str,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<Vec<LiveStream>> {
+        let Value::GameId(game_id) = self.get_game_id(category_name, client_id, token).await?
+        else {
+            eyre::bail!("expected game id");
+        };
 
-                            let resp = http.execute(req).await.unwrap().bytes().await.unwrap();
+        let url = format!("https://api.twitch.tv/helix/streams?game_id={game_id}&first=20");
+        let req = self
+            .http
+            .get(url)
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .build()?;
 
-                            Emote::transcode_from_bytes(emote.name, &resp)
-                        })
-                    })
-                    .buffer_unordered(5);
-
-                let set = set
-                    .fold(
-                        (
-                            Vec::<Emote>::with_capacity(emote_count),
-                            Vec::<RawEmote>::with_capacity(emote_count),
-                        ),
-                        |mut s, emote_resp| async move {
-                            if let Ok(Ok((emote, image))) = emote_resp {
-                                s.1.push(emote);
-                                s.0.push(image);
-                            }
-                            s
-                        },
-                    )
-                    .await;
-
-                Ok(RawCacheValue::EmoteSet(set.1))
-            },
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .json_or_warn::<response::twitch::Streams>()
+            .await?;
+
+        Ok(resp
+            .data
+            .into_iter()
+            .map(|stream| LiveStream {
+                login: stream.user_login,
+                title: stream.title,
+                viewers: stream.viewer_count,
+            })
+            .collect())
+    }
+
+    /// Fetches drop campaigns running right now, this is never cached
+    ///
+    /// Helix has no endpoint listing campaigns, so this goes through Twitch's GQL api
+    pub async fn get_active_drops(
+        &self,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<Vec<DropCampaign>> {
+        let query = "query { currentUser { dropCampaigns { name status endAt game { displayName } \
+                     timeBasedDrops { name requiredMinutesWatched } } } }";
+        let req = self
+            .http
+            .post("https://gql.twitch.tv/gql")
+            .header("Authorization", format!("OAuth {token}"))
+            .header("Client-Id", client_id)
+            .json(&serde_json::json!({ "query": query }))
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .json_or_warn::<response::twitch::Drops>()
+            .await?;
+
+        Ok(resp
+            .data
+            .current_user
+            .ok_or_eyre("not logged in")?
+            .drop_campaigns
+            .into_iter()
+            .filter(|campaign| campaign.status == "ACTIVE")
+            .map(|campaign| DropCampaign {
+                name: campaign.name,
+                game: campaign.game.display_name,
+                end: campaign.end_at.into(),
+                drops: campaign
+                    .time_based_drops
+                    .into_iter()
+                    .map(|drop| (drop.name, drop.required_minutes_watched))
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Live streams among `channels`, keyed by login. Offline channels are left out, this is
+    /// never cached
+    ///
+    /// Streams are looked up by `broadcaster_ids`, falling back to channel logins when that fails
+    pub async fn get_stream_statuses(
+        &self,
+        channels: &[(&str, &str)],
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<HashMap<String, StreamStatus>> {
+        let by_id: Vec<_> = channels.iter().map(|(_, id)| ("user_id", *id)).collect();
+        match self.get_streams(&by_id, client_id, token).await {
+            Ok(streams) => Ok(streams),
+            Err(_) => {
+                let by_login: Vec<_> = channels
+                    .iter()
+                    .map(|(login, _)| ("user_login", *login))
+                    .collect();
+                self.get_streams(&by_login, client_id, token).await
+            }
+        }
+    }
+
+    async fn get_streams(
+        &self,
+        query: &[(&str, &str)],
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<HashMap<String, StreamStatus>> {
+        let req = self
+            .http
+            .get("https://api.twitch.tv/helix/streams")
+            .query(query)
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .error_for_status()?
+            .json_or_warn::<response::twitch::Streams>()
+            .await?;
+
+        Ok(resp
+            .data
+            .into_iter()
+            .map(|stream| {
+                (
+                    stream.user_login,
+                    StreamStatus {
+                        started_at: stream.started_at.into(),
+                        viewers: stream.viewer_count,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Fetches the current stream title and category, this is never cached
+    pub async fn get_channel_info(
+        &self,
+        client_id: &str,
+        broadcaster_id: &str,
+        token: &str,
+    ) -> eyre::Result<response::twitch::ChannelData> {
+        let url = format!("https://api.twitch.tv/helix/channels?broadcaster_id={broadcaster_id}");
+        let req = self
+            .http
+            .get(url)
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .json_or_warn::<response::twitch::Channel>()
+            .await?;
+
+        resp.data.into_iter().next().ok_or_eyre("channel not found")
+    }
+
+    /// Bans `user_id`, or times them out for `duration` seconds
+    pub async fn ban_user(
+        &self,
+        broadcaster_id: &str,
+        moderator_id: &str,
+        user_id: &str,
+        duration: Option<u32>,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<()> {
+        let url = format!(
+            "https://api.twitch.tv/helix/moderation/bans?broadcaster_id={broadcaster_id}&moderator_id={moderator_id}"
+        );
+        let mut data = serde_json::json!({ "user_id": user_id });
+        if let Some(duration) = duration {
+            data["duration"] = duration.into();
+        }
+
+        let req = self
+            .http
+            .post(url)
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .json(&serde_json::json!({ "data": data }))
+            .build()?;
+
+        self.http.execute(req).await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Starts a poll in the broadcaster's channel and returns its id
+    pub async fn create_poll(
+        &self,
+        broadcaster_id: &str,
+        title: &str,
+        choices: &[String],
+        duration: u32,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<String> {
+        let choices: Vec<_> = choices
+            .iter()
+            .map(|choice| serde_json::json!({ "title": choice }))
+            .collect();
+        let req = self
+            .http
+            .post("https://api.twitch.tv/helix/polls")
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .json(&serde_json::json!({
+                "broadcaster_id": broadcaster_id,
+                "title": title,
+                "choices": choices,
+                "duration": duration,
+            }))
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .error_for_status()?
+            .json_or_warn::<response::twitch::Polls>()
+            .await?;
+
+        Ok(resp
+            .data
+            .into_iter()
+            .next()
+            .ok_or_eyre("no poll created")?
+            .id)
+    }
+
+    /// Custom channel points rewards created with our client id, the only ones Twitch lets us
+    /// update. Only available to the broadcaster and their editors
+    pub async fn get_channel_rewards(
+        &self,
+        broadcaster_id: &str,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<Vec<CustomReward>> {
+        let req = self
+            .http
+            .get("https://api.twitch.tv/helix/channel_points/custom_rewards")
+            .query(&[
+                ("broadcaster_id", broadcaster_id),
+                ("only_manageable_rewards", "true"),
+            ])
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .error_for_status()?
+            .json_or_warn::<response::twitch::CustomRewards>()
+            .await?;
+
+        Ok(resp
+            .data
+            .into_iter()
+            .map(|reward| CustomReward {
+                id: reward.id,
+                title: reward.title,
+                cost: reward.cost,
+                enabled: reward.is_enabled,
+            })
+            .collect())
+    }
+
+    pub async fn set_reward_enabled(
+        &self,
+        broadcaster_id: &str,
+        reward_id: &str,
+        enabled: bool,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<()> {
+        let url = format!(
+            "https://api.twitch.tv/helix/channel_points/custom_rewards?broadcaster_id={broadcaster_id}&id={reward_id}"
+        );
+        let req = self
+            .http
+            .patch(url)
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .json(&serde_json::json!({ "is_enabled": enabled }))
+            .build()?;
+
+        self.http.execute(req).await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Runs an ad break of `length` seconds and returns the length Twitch actually started
+    pub async fn start_commercial(
+        &self,
+        broadcaster_id: &str,
+        length: u32,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<u32> {
+        let req = self
+            .http
+            .post("https://api.twitch.tv/helix/channels/commercial")
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .json(&serde_json::json!({
+                "broadcaster_id": broadcaster_id,
+                "length": length,
+            }))
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .error_for_status()?
+            .json_or_warn::<response::twitch::Commercials>()
+            .await?;
+
+        Ok(resp
+            .data
+            .into_iter()
+            .next()
+            .ok_or_eyre("no commercial started")?
+            .length)
+    }
+
+    /// Grants or removes VIP status, only works for the broadcaster or their editors
+    pub async fn set_vip(
+        &self,
+        broadcaster_id: &str,
+        user_id: &str,
+        vip: bool,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<()> {
+        let url = format!(
+            "https://api.twitch.tv/helix/channels/vips?broadcaster_id={broadcaster_id}&user_id={user_id}"
+        );
+        let req = if vip {
+            self.http.post(url)
+        } else {
+            self.http.delete(url)
+        }
+        .bearer_auth(token)
+        .header("Client-Id", client_id)
+        .build()?;
+
+        self.http.execute(req).await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Fetches upcoming stream segments, this is never cached
+    pub async fn get_schedule(
+        &self,
+        broadcaster_id: &str,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<Vec<ScheduleSegment>> {
+        let url = format!("https://api.twitch.tv/helix/schedule?broadcaster_id={broadcaster_id}");
+        let req = self
+            .http
+            .get(url)
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .error_for_status()?
+            .json_or_warn::<response::twitch::Schedule>()
+            .await?;
+
+        Ok(resp
+            .data
+            .segments
+            .unwrap_or_default()
+            .into_iter()
+            .map(|segment| ScheduleSegment {
+                start: segment.start_time.with_timezone(&Local),
+                duration: segment
+                    .end_time
+                    .map(|end| end - segment.start_time)
+                    .unwrap_or_default(),
+                title: segment.title,
+                category: segment.category.map(|c| c.name),
+            })
+            .collect())
+    }
+
+    /// Channel logins matching `query`, results are reused for 30 seconds
+    pub async fn search_channels(
+        &mut self,
+        query: &str,
+        client_id: &str,
+        token: &str,
+    ) -> eyre::Result<Vec<String>> {
+        if let Some((fetched, channels)) = self.channel_search.get(query) {
+            if fetched.elapsed() < Duration::from_secs(30) {
+                return Ok(channels.clone());
+            }
+        }
+
+        let req = self
+            .http
+            .get("https://api.twitch.tv/helix/search/channels")
+            .query(&[("query", query), ("first", "5")])
+            .bearer_auth(token)
+            .header("Client-Id", client_id)
+            .build()?;
+
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .json_or_warn::<response::twitch::SearchChannels>()
+            .await?;
+
+        let channels: Vec<String> = resp.data.into_iter().map(|c| c.broadcaster_login).collect();
+        self.channel_search
+            .put(query.into(), (Instant::now(), channels.clone()));
+        Ok(channels)
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    pub async fn get_global_emotes(
+        &mut self,
+        client_id: String,
+        token: String,
+        animated: bool,
+    ) -> eyre::Result<&Value> {
+        self.get_emotes(
+            "https://api.twitch.tv/helix/chat/emotes/global".into(),
+            client_id,
+            token,
+            animated,
+            Some(GLOBAL_EMOTE_TTL),
         )
         .await
     }
+
+    /// Subscriber, follower and bits emotes of a channel
+    pub async fn get_channel_emotes(
+        &mut self,
+        broadcaster_id: &str,
+        client_id: String,
+        token: String,
+        animated: bool,
+    ) -> eyre::Result<&Value> {
+        self.get_emotes(
+            Self::channel_emotes_url(broadcaster_id),
+            client_id,
+            token,
+            animated,
+            None,
+        )
+        .await
+    }
+
+    /// Also the cache key of the channel's static emote set, the animated one adds `#animated`,
+    /// both before going through `emote_set_key`
+    pub fn channel_emotes_url(broadcaster_id: &str) -> String {
+        format!("https://api.twitch.tv/helix/chat/emotes?broadcaster_id={broadcaster_id}")
+    }
+
+    /// Cache keys of a channel's emote sets from every provider
+    pub fn channel_emote_set_keys(&self, broadcaster_id: &str) -> Vec<String> {
+        let url = Self::channel_emotes_url(broadcaster_id);
+        [
+            format!("{url}#animated"),
+            url,
+            Self::seventv_channel_url(broadcaster_id),
+            Self::bttv_channel_url(broadcaster_id),
+            Self::ffz_channel_url(broadcaster_id),
+        ]
+        .iter()
+        .map(|url| self.emote_set_key(url))
+        .collect()
+    }
+
+    fn seventv_channel_url(user_id: &str) -> String {
+        format!("https://7tv.io/v3/users/twitch/{user_id}")
+    }
+
+    fn bttv_channel_url(channel_id: &str) -> String {
+        format!("https://api.betterttv.net/3/cached/users/twitch/{channel_id}")
+    }
+
+    fn ffz_channel_url(channel_id: &str) -> String {
+        format!("https://api.frankerfacez.com/v1/room/id/{channel_id}")
+    }
+
+    /// Cache key of the emote set at `url` in the configured scale, 1x sets keep the plain url
+    pub fn emote_set_key(&self, url: &str) -> String {
+        match self.emote_scale {
+            EmoteScale::One => url.into(),
+            scale => format!("{url}@{}x", scale.factor()),
+        }
+    }
+
+    /// Emote sets from `provider` are cached for `emote_ttl_{provider}_seconds`, or `default_ttl`
+    fn emote_set_descriptor<'a>(
+        &self,
+        key: &'a str,
+        provider: Provider,
+        default_ttl: Option<Duration>,
+    ) -> CacheValueDescriptor<'a> {
+        CacheValueDescriptor {
+            use_disk_cache: true,
+            key,
+            ttl: self.emote_ttl.get(&provider).copied().or(default_ttl),
+        }
+    }
+
+    /// With `animated`, emotes that have an animated variant are downloaded in that format.
+    /// `default_ttl` applies when `emote_ttl_twitch_seconds` isn't set
+    async fn get_emotes(
+        &mut self,
+        url: String,
+        client_id: String,
+        token: String,
+        animated: bool,
+        default_ttl: Option<Duration>,
+    ) -> eyre::Result<&Value> {
+        // Static and animated sets are cached separately
+        let key = if animated {
+            self.emote_set_key(&format!("{url}#animated"))
+        } else {
+            self.emote_set_key(&url)
+        };
+        let descriptor = self.emote_set_descriptor(&key, Provider::Twitch, default_ttl);
+        if self.read_cached_emote_set(descriptor.key).await? {
+            return Ok(&self.lru.get(&key).unwrap().1);
+        }
+
+        let req = self
+            .http
+            .get(&url)
+            .bearer_auth(&token)
+            .header("Client-Id", client_id)
+            .build()?;
+        let resp = self
+            .http
+            .execute(req)
+            .await?
+            .json_or_warn::<response::twitch::GlobalEmotes>()
+            .await?;
+
+        let scale = self.emote_scale;
+        let sources = resp
+            .data
+            .into_iter()
+            .map(|emote| {
+                if animated && emote.format.iter().any(|f| f == "animated") {
+                    EmoteSource {
+                        key: format!("emote/{}#animated", emote.id),
+                        url: resp
+                            .template
+                            .replace("{{id}}", &emote.id)
+                            .replace("{{format}}", "animated")
+                            .replace("{{theme_mode}}", "dark")
+                            .replace("{{scale}}", &format!("{}.0", scale.factor())),
+                        name: emote.name,
+                    }
+                } else {
+                    EmoteSource {
+                        key: format!("emote/{}", emote.id),
+                        url: emote
+                            .images
+                            .get(&format!("url_{}x", scale.twitch_factor()))
+                            .or_else(|| emote.images.get("url_1x"))
+                            .unwrap()
+                            .clone(),
+                        name: emote.name,
+                    }
+                }
+            })
+            .collect();
+        self.download_emote_set(descriptor, sources).await
+    }
+
+    /// Emotes in the global 7TV set
+    pub async fn get_7tv_global_emotes(&mut self) -> eyre::Result<&Value> {
+        let url = "https://7tv.io/v3/emote-sets/global";
+        let key = self.emote_set_key(url);
+        let descriptor =
+            self.emote_set_descriptor(&key, Provider::SevenTv, Some(SEVENTV_EMOTE_TTL));
+        if self.read_cached_emote_set(descriptor.key).await? {
+            return Ok(&self.lru.get(&key).unwrap().1);
+        }
+
+        let scale = self.emote_scale;
+        let set = self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .json_or_warn::<response::seventv::EmoteSet>()
+            .await?;
+        self.download_emote_set(descriptor, set.sources(scale))
+            .await
+    }
+
+    /// 7TV emotes of the channel with the twitch id `user_id`, empty when it has no 7TV account
+    pub async fn get_7tv_channel_emotes(&mut self, user_id: &str) -> eyre::Result<&Value> {
+        let url = Self::seventv_channel_url(user_id);
+        let key = self.emote_set_key(&url);
+        let descriptor =
+            self.emote_set_descriptor(&key, Provider::SevenTv, Some(SEVENTV_EMOTE_TTL));
+        if self.read_cached_emote_set(descriptor.key).await? {
+            return Ok(&self.lru.get(&key).unwrap().1);
+        }
+
+        let scale = self.emote_scale;
+        let resp = self.http.get(&url).send().await?;
+        let sources = if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            Vec::new()
+        } else {
+            resp.json_or_warn::<response::seventv::User>()
+                .await?
+                .emote_set
+                .map_or_else(Vec::new, |set| set.sources(scale))
+        };
+        self.download_emote_set(descriptor, sources).await
+    }
+
+    /// Emotes BTTV offers in every channel
+    pub async fn get_bttv_global_emotes(&mut self) -> eyre::Result<&Value> {
+        let url = "https://api.betterttv.net/3/cached/emotes/global";
+        let key = self.emote_set_key(url);
+        let descriptor = self.emote_set_descriptor(&key, Provider::Bttv, Some(BTTV_EMOTE_TTL));
+        if self.read_cached_emote_set(descriptor.key).await? {
+            return Ok(&self.lru.get(&key).unwrap().1);
+        }
+
+        let scale = self.emote_scale;
+        let emotes = self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .json_or_warn::<Vec<response::bttv::Emote>>()
+            .await?;
+        let sources = emotes
+            .into_iter()
+            .map(|emote| emote.source(scale))
+            .collect();
+        self.download_emote_set(descriptor, sources).await
+    }
+
+    /// Own and shared BTTV emotes of the channel with the twitch id `channel_id`, empty when it
+    /// has no BTTV account
+    pub async fn get_bttv_channel_emotes(&mut self, channel_id: &str) -> eyre::Result<&Value> {
+        let url = Self::bttv_channel_url(channel_id);
+        let key = self.emote_set_key(&url);
+        let descriptor = self.emote_set_descriptor(&key, Provider::Bttv, Some(BTTV_EMOTE_TTL));
+        if self.read_cached_emote_set(descriptor.key).await? {
+            return Ok(&self.lru.get(&key).unwrap().1);
+        }
+
+        let scale = self.emote_scale;
+        let resp = self.http.get(&url).send().await?;
+        let sources = if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            Vec::new()
+        } else {
+            let user = resp.json_or_warn::<response::bttv::User>().await?;
+            user.channel_emotes
+                .into_iter()
+                .chain(user.shared_emotes)
+                .map(|emote| emote.source(scale))
+                .collect()
+        };
+        self.download_emote_set(descriptor, sources).await
+    }
+
+    /// Emotes of the FFZ default sets, offered in every channel
+    pub async fn get_ffz_global_emotes(&mut self) -> eyre::Result<&Value> {
+        let url = "https://api.frankerfacez.com/v1/set/global";
+        let key = self.emote_set_key(url);
+        let descriptor = self.emote_set_descriptor(&key, Provider::Ffz, Some(FFZ_EMOTE_TTL));
+        if self.read_cached_emote_set(descriptor.key).await? {
+            return Ok(&self.lru.get(&key).unwrap().1);
+        }
+
+        let scale = self.emote_scale;
+        let mut global = self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .json_or_warn::<response::ffz::GlobalSets>()
+            .await?;
+        // The other sets are only available to some users
+        let sources = global
+            .default_sets
+            .iter()
+            .filter_map(|id| global.sets.remove(&id.to_string()))
+            .flat_map(|set| set.sources(scale))
+            .collect();
+        self.download_emote_set(descriptor, sources).await
+    }
+
+    /// FFZ emotes of the channel with the twitch id `channel_id`, empty when it has no FFZ room
+    pub async fn get_ffz_channel_emotes(&mut self, channel_id: &str) -> eyre::Result<&Value> {
+        let url = Self::ffz_channel_url(channel_id);
+        let key = self.emote_set_key(&url);
+        let descriptor = self.emote_set_descriptor(&key, Provider::Ffz, Some(FFZ_EMOTE_TTL));
+        if self.read_cached_emote_set(descriptor.key).await? {
+            return Ok(&self.lru.get(&key).unwrap().1);
+        }
+
+        let scale = self.emote_scale;
+        let resp = self.http.get(&url).send().await?;
+        let sources = if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            Vec::new()
+        } else {
+            resp.json_or_warn::<response::ffz::Room>()
+                .await?
+                .sets
+                .into_values()
+                .flat_map(|set| set.sources(scale))
+                .collect()
+        };
+        self.download_emote_set(descriptor, sources).await
+    }
+
+    /// Puts the set cached under `key` in memory, `false` when it has to be fetched again.
+    /// Emotes that failed to download last time are retried
+    async fn read_cached_emote_set(&mut self, key: &str) -> eyre::Result<bool> {
+        if self.read_cache(false, key).await?.is_some() {
+            return Ok(true);
+        }
+        let Some((expires_at, RawCacheValue::EmoteManifest(sources))) =
+            self.read_disk_value(key).await
+        else {
+            return Ok(false);
+        };
+        self.load_emote_set(key, sources, expires_at, false).await?;
+        Ok(true)
+    }
+
+    /// Caches a freshly fetched set as described by `descriptor`
+    async fn download_emote_set(
+        &mut self,
+        descriptor: CacheValueDescriptor<'_>,
+        mut sources: Vec<EmoteSource>,
+    ) -> eyre::Result<&Value> {
+        // Each scale is its own image
+        if self.emote_scale != EmoteScale::One {
+            for source in &mut sources {
+                source.key = format!("{}@{}x", source.key, self.emote_scale.factor());
+            }
+        }
+        let expires_at = descriptor.ttl.map(|ttl| SystemTime::now() + ttl);
+        self.load_emote_set(descriptor.key, sources, expires_at, true)
+            .await
+    }
+
+    /// Reads the emotes of a set from the disk cache and downloads the missing ones. With
+    /// `write_manifest`, the set is cached under `manifest_key` as its emote sources, failed
+    /// downloads included so they're retried on the next load
+    async fn load_emote_set(
+        &mut self,
+        manifest_key: &str,
+        sources: Vec<EmoteSource>,
+        expires_at: Option<SystemTime>,
+        write_manifest: bool,
+    ) -> eyre::Result<&Value> {
+        let mut emotes = Vec::with_capacity(sources.len());
+        let mut missing = Vec::new();
+        for source in &sources {
+            let cached = match self.read_disk_value(&source.key).await {
+                Some((_, RawCacheValue::Emote(mut raw))) => {
+                    // Images are shared between sets, names are per set, like 7TV aliases
+                    raw.name.clone_from(&source.name);
+                    // Images that no longer decode are downloaded again
+                    Emote::try_from(raw).ok()
+                }
+                _ => None,
+            };
+            if let Some(emote) = cached {
+                metrics::inc(&metrics::EMOTE_CACHE_HITS);
+                emotes.push(emote);
+            } else {
+                metrics::inc(&metrics::EMOTE_CACHE_MISSES);
+                missing.push(source.clone());
+            }
+        }
+
+        let http = self.http.clone();
+        let mut downloads = futures::stream::iter(missing)
+            .map(|source| {
+                let http = http.clone();
+                tokio::spawn(async move {
+                    let bytes = http.get(source.url).send().await?.bytes().await?;
+                    let (raw, emote) = Emote::transcode_from_bytes(source.name, &bytes)?;
+                    eyre::Ok((source.key, raw, emote))
+                })
+            })
+            .buffer_unordered(self.emote_download_concurrency);
+
+        while let Some(download) = downloads.next().await {
+            match download {
+                Ok(Ok((key, raw, emote))) => {
+                    // Emote images never change, only sets do
+                    self.write_disk_cache(&key, RawCacheValue::Emote(raw), None)
+                        .await?;
+                    emotes.push(emote);
+                }
+                Ok(Err(e)) => tracing::warn!(error = %e, "emote download failed"),
+                Err(e) => tracing::warn!(error = %e, "emote download panicked"),
+            }
+        }
+
+        // An existing manifest keeps its expiry
+        if write_manifest {
+            self.write_disk_cache(
+                manifest_key,
+                RawCacheValue::EmoteManifest(sources),
+                expires_at,
+            )
+            .await?;
+        }
+        self.lru
+            .put(manifest_key.into(), (expires_at, Value::EmoteSet(emotes)));
+        self.update_lru_size();
+        Ok(&self.lru.get(manifest_key).unwrap().1)
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        self.lru.clear();
+        self.update_lru_size();
+    }
+}
+
+/// Emote to download, from any provider
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[archive(check_bytes)]
+pub struct EmoteSource {
+    /// Disk cache key of the image
+    key: String,
+    name: String,
+    url: String,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct ScheduleSegment {
+    pub start: DateTime<Local>,
+    pub duration: TimeDelta,
+    pub title: String,
+    pub category: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct LiveStream {
+    pub login: String,
+    pub title: String,
+    pub viewers: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct StreamStatus {
+    pub started_at: DateTime<Local>,
+    pub viewers: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct CustomReward {
+    pub id: String,
+    pub title: String,
+    pub cost: u64,
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct DropCampaign {
+    pub name: String,
+    pub game: String,
+    pub end: DateTime<Local>,
+    /// Reward names and the minutes watched needed for each
+    pub drops: Vec<(String, u32)>,
+}
+
+/// Lookups since startup, disk usage is as of the last `Cache::count_disk_usage`
+#[derive(Clone, Default, PartialEq, PartialOrd)]
+pub struct CacheStats {
+    pub lru_hits: u64,
+    pub lru_misses: u64,
+    pub disk_hits: u64,
+    pub disk_misses: u64,
+    pub lru_size: usize,
+    pub disk_entry_count: usize,
+    pub disk_bytes: u64,
+}
+
+impl CacheStats {
+    /// One line each for memory, disk lookups and disk usage
+    pub fn summary(&self) -> String {
+        let rate = |hits: u64, misses: u64| match hits + misses {
+            0 => 0.0,
+            total => hits as f64 * 100.0 / total as f64,
+        };
+        format!(
+            "Memory: {} hits, {} misses ({:.1}% hit rate), {} entries\n\
+             Disk: {} hits, {} misses ({:.1}% hit rate)\n\
+             Disk usage: {} entries, {:.1} MiB",
+            self.lru_hits,
+            self.lru_misses,
+            rate(self.lru_hits, self.lru_misses),
+            self.lru_size,
+            self.disk_hits,
+            self.disk_misses,
+            rate(self.disk_hits, self.disk_misses),
+            self.disk_entry_count,
+            self.disk_bytes as f64 / (1024.0 * 1024.0),
+        )
+    }
 }
 
 struct CacheValueDescriptor<'a> {
     use_disk_cache: bool,
     key: &'a str,
+    /// Stored with the entry as when it expires, `None` keeps it forever
+    ttl: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -247,47 +1335,152 @@ pub enum Value {
     ClientId(String),
     UserId(String),
     EmoteSet(Vec<Emote>),
+    GameId(String),
 }
 
-impl From<RawCacheValue> for Value {
-    fn from(value: RawCacheValue) -> Self {
-        match value {
+/// Emotes are only cached on disk, sets are put together by `Cache::get_emotes`
+impl TryFrom<RawCacheValue> for Value {
+    type Error = eyre::Report;
+
+    fn try_from(value: RawCacheValue) -> eyre::Result<Self> {
+        Ok(match value {
             RawCacheValue::ClientId(v) => Value::ClientId(v),
             RawCacheValue::UserId(v) => Value::UserId(v),
-            RawCacheValue::EmoteSet(v) => {
-                Value::EmoteSet(v.into_iter().map(|e| e.into()).collect())
+            RawCacheValue::GameId(v) => Value::GameId(v),
+            RawCacheValue::EmoteManifest(_) | RawCacheValue::Emote(_) => {
+                eyre::bail!("emotes can't be cached as values")
             }
-        }
+        })
     }
 }
 
-#[derive(Debug)]
-pub struct Emote {
-    name: String,
-    image: DynamicImage,
+#[derive(Clone, Debug, PartialEq)]
+pub enum Emote {
+    Static { name: String, image: DynamicImage },
+    Animated(AnimatedEmote),
 }
 
-impl From<RawEmote> for Emote {
-    fn from(value: RawEmote) -> Self {
-        let image =
-            image::load_from_memory_with_format(&value.data, image::ImageFormat::WebP).unwrap();
+/// Frames of a GIF or animated WebP emote
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimatedEmote {
+    pub name: String,
+    pub frames: Vec<DynamicImage>,
+    /// How long each frame is shown
+    pub frame_delay_ms: Vec<u32>,
+}
 
-        Self {
+/// Frames shown for less than this are slowed down to `DEFAULT_FRAME_DELAY_MS`, like browsers do
+const MIN_FRAME_DELAY_MS: u32 = 20;
+const DEFAULT_FRAME_DELAY_MS: u32 = 100;
+
+/// Images have no ordering, emotes are ordered by name alone
+impl PartialOrd for Emote {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.name().partial_cmp(other.name())
+    }
+}
+
+impl TryFrom<RawEmote> for Emote {
+    type Error = eyre::Report;
+
+    fn try_from(value: RawEmote) -> eyre::Result<Self> {
+        if value.animated {
+            if let Ok(Some(animation)) = Self::decode_animation(value.name.clone(), &value.data) {
+                return Ok(Self::Animated(animation));
+            }
+        }
+        let image = if value.animated {
+            image::load_from_memory(&value.data)?
+        } else {
+            image::load_from_memory_with_format(&value.data, image::ImageFormat::WebP)?
+        };
+
+        Ok(Self::Static {
             name: value.name,
             image,
-        }
+        })
     }
 }
 
 impl Emote {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Static { name, .. } => name,
+            Self::Animated(animation) => &animation.name,
+        }
+    }
+
     /// Returns both RawEmote and Emote for caching
     fn transcode_from_bytes(name: String, bytes: &[u8]) -> eyre::Result<(RawEmote, Emote)> {
-        let image = image::load_from_memory(bytes).unwrap();
+        // Animations are cached as downloaded, the image crate can't encode animated WebP
+        if let Some(animation) = Self::decode_animation(name.clone(), bytes)? {
+            let raw = RawEmote {
+                name,
+                data: bytes.to_vec(),
+                animated: true,
+            };
+            return Ok((raw, Emote::Animated(animation)));
+        }
+
+        let image = image::load_from_memory(bytes)?;
         Ok((
             RawEmote::from_image(name.clone(), &image)?,
-            Emote { name, image },
+            Emote::Static { name, image },
         ))
     }
+
+    /// `None` for single frame images
+    fn decode_animation(name: String, bytes: &[u8]) -> eyre::Result<Option<AnimatedEmote>> {
+        let frames = match image::guess_format(bytes)? {
+            image::ImageFormat::Gif => {
+                image::codecs::gif::GifDecoder::new(Cursor::new(bytes))?.into_frames()
+            }
+            image::ImageFormat::WebP => {
+                let decoder = image::codecs::webp::WebPDecoder::new(Cursor::new(bytes))?;
+                if !decoder.has_animation() {
+                    return Ok(None);
+                }
+                decoder.into_frames()
+            }
+            _ => return Ok(None),
+        }
+        .collect_frames()?;
+        if frames.len() < 2 {
+            return Ok(None);
+        }
+
+        let (frames, frame_delay_ms) = frames
+            .into_iter()
+            .map(|frame| {
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                let delay = numer / denom.max(1);
+                let delay = if delay < MIN_FRAME_DELAY_MS {
+                    DEFAULT_FRAME_DELAY_MS
+                } else {
+                    delay
+                };
+                (DynamicImage::ImageRgba8(frame.into_buffer()), delay)
+            })
+            .unzip();
+        Ok(Some(AnimatedEmote {
+            name,
+            frames,
+            frame_delay_ms,
+        }))
+    }
+}
+
+/// Disk cache entry, with when it stops being valid
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[archive(check_bytes)]
+pub struct TimestampedRawCacheValue {
+    /// Milliseconds since the unix epoch, `None` never expires
+    expires_at: Option<u64>,
+    value: RawCacheValue,
+}
+
+fn is_expired(expires_at: Option<SystemTime>) -> bool {
+    expires_at.is_some_and(|expires_at| SystemTime::now() >= expires_at)
 }
 
 #[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -295,15 +1488,20 @@ impl Emote {
 pub enum RawCacheValue {
     ClientId(String),
     UserId(String),
-    EmoteSet(Vec<RawEmote>),
+    GameId(String),
+    /// Emote set, as where each of its emotes is cached and downloaded from
+    EmoteManifest(Vec<EmoteSource>),
+    Emote(RawEmote),
 }
 
 #[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[archive(check_bytes)]
 pub struct RawEmote {
     name: String,
+    /// WebP, or the downloaded GIF or WebP file for animated emotes
     #[with(CopyOptimize)]
     data: Vec<u8>,
+    animated: bool,
 }
 
 impl RawEmote {
@@ -311,15 +1509,20 @@ impl RawEmote {
         let mut data = Vec::new();
         image.write_to(&mut Cursor::new(&mut data), image::ImageFormat::WebP)?;
 
-        Ok(Self { data, name })
+        Ok(Self {
+            data,
+            name,
+            animated: false,
+        })
     }
 }
 
-mod response {
+pub mod response {
 
     pub mod twitch {
         use std::collections::HashMap;
 
+        use chrono::{DateTime, Utc};
         use serde::Deserialize;
 
         #[derive(Deserialize)]
@@ -337,16 +1540,313 @@ mod response {
             pub id: String,
         }
 
+        #[derive(Deserialize)]
+        pub struct Channel {
+            pub data: Vec<ChannelData>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct ChannelData {
+            pub title: String,
+            pub game_name: String,
+            /// Stream delay in seconds, always 0 unless the token belongs to the broadcaster
+            #[serde(default)]
+            pub delay: u64,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Schedule {
+            pub data: ScheduleData,
+        }
+
+        #[derive(Deserialize)]
+        pub struct ScheduleData {
+            pub segments: Option<Vec<ScheduleSegment>>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct ScheduleSegment {
+            pub start_time: DateTime<Utc>,
+            pub end_time: Option<DateTime<Utc>>,
+            pub title: String,
+            pub category: Option<ScheduleCategory>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct ScheduleCategory {
+            pub name: String,
+        }
+
+        #[derive(Deserialize)]
+        pub struct SearchChannels {
+            pub data: Vec<SearchChannelData>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct SearchChannelData {
+            pub broadcaster_login: String,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Games {
+            pub data: Vec<Game>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Game {
+            pub id: String,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Streams {
+            pub data: Vec<Stream>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Stream {
+            pub user_login: String,
+            pub started_at: DateTime<Utc>,
+            pub title: String,
+            pub viewer_count: u64,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Drops {
+            pub data: DropsData,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct DropsData {
+            pub current_user: Option<DropsUser>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct DropsUser {
+            pub drop_campaigns: Vec<DropCampaign>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct DropCampaign {
+            pub name: String,
+            pub status: String,
+            pub end_at: DateTime<Utc>,
+            pub game: DropGame,
+            pub time_based_drops: Vec<TimeBasedDrop>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct DropGame {
+            pub display_name: String,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct TimeBasedDrop {
+            pub name: String,
+            pub required_minutes_watched: u32,
+        }
+
+        #[derive(Deserialize)]
+        pub struct CustomRewards {
+            pub data: Vec<CustomReward>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct CustomReward {
+            pub id: String,
+            pub title: String,
+            pub cost: u64,
+            pub is_enabled: bool,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Commercials {
+            pub data: Vec<Commercial>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Commercial {
+            pub length: u32,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Polls {
+            pub data: Vec<Poll>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Poll {
+            pub id: String,
+        }
+
         #[derive(Deserialize)]
         pub struct GlobalEmotes {
             pub data: Vec<GlobalEmoteData>,
+            /// Image url with `{{id}}`, `{{format}}`, `{{theme_mode}}` and `{{scale}}` placeholders
+            pub template: String,
         }
 
         #[derive(Deserialize)]
         pub struct GlobalEmoteData {
-            pub name: String,
             pub id: String,
+            pub name: String,
             pub images: HashMap<String, String>,
+            /// `static`, and `animated` when the emote has an animated variant
+            pub format: Vec<String>,
+        }
+    }
+
+    pub mod seventv {
+        use serde::Deserialize;
+
+        use super::super::EmoteSource;
+        use crate::config::EmoteScale;
+
+        #[derive(Deserialize)]
+        pub struct User {
+            /// `None` when the user hasn't picked an emote set
+            pub emote_set: Option<EmoteSet>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct EmoteSet {
+            #[serde(default)]
+            pub emotes: Vec<ActiveEmote>,
+        }
+
+        impl EmoteSet {
+            pub(in crate::request) fn sources(self, scale: EmoteScale) -> Vec<EmoteSource> {
+                self.emotes
+                    .into_iter()
+                    .map(|emote| EmoteSource {
+                        key: format!("emote/7tv/{}", emote.id),
+                        url: format!("https:{}/{}x.webp", emote.data.host.url, scale.factor()),
+                        name: emote.name,
+                    })
+                    .collect()
+            }
+        }
+
+        /// Emote as added to a set, `name` can differ from the emote's own name
+        #[derive(Deserialize)]
+        pub struct ActiveEmote {
+            pub id: String,
+            pub name: String,
+            pub data: EmoteData,
+        }
+
+        #[derive(Deserialize)]
+        pub struct EmoteData {
+            pub host: ImageHost,
+        }
+
+        #[derive(Deserialize)]
+        pub struct ImageHost {
+            /// Protocol relative url of the directory holding `1x.webp` and the other sizes
+            pub url: String,
+        }
+    }
+
+    pub mod bttv {
+        use serde::Deserialize;
+
+        use super::super::EmoteSource;
+        use crate::config::EmoteScale;
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct User {
+            #[serde(default)]
+            pub channel_emotes: Vec<Emote>,
+            /// Emotes made by others that the channel added
+            #[serde(default)]
+            pub shared_emotes: Vec<Emote>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Emote {
+            pub id: String,
+            pub code: String,
+        }
+
+        impl Emote {
+            pub(in crate::request) fn source(self, scale: EmoteScale) -> EmoteSource {
+                EmoteSource {
+                    key: format!("emote/bttv/{}", self.id),
+                    url: format!(
+                        "https://cdn.betterttv.net/emote/{}/{}x",
+                        self.id,
+                        scale.factor()
+                    ),
+                    name: self.code,
+                }
+            }
+        }
+    }
+
+    pub mod ffz {
+        use std::collections::HashMap;
+
+        use serde::Deserialize;
+
+        use super::super::EmoteSource;
+        use crate::config::EmoteScale;
+
+        #[derive(Deserialize)]
+        pub struct GlobalSets {
+            /// Ids of the sets everyone can use
+            pub default_sets: Vec<u64>,
+            /// Sets by id
+            pub sets: HashMap<String, EmoteSet>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Room {
+            /// Sets by id, usually only the room's own set
+            pub sets: HashMap<String, EmoteSet>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct EmoteSet {
+            #[serde(default)]
+            pub emoticons: Vec<Emote>,
+        }
+
+        impl EmoteSet {
+            pub(in crate::request) fn sources(self, scale: EmoteScale) -> Vec<EmoteSource> {
+                self.emoticons
+                    .into_iter()
+                    .filter_map(|emote| {
+                        // Only some emotes have the bigger sizes
+                        let url = emote
+                            .urls
+                            .get(&scale.twitch_factor().to_string())
+                            .or_else(|| emote.urls.get("1"))?;
+                        Some(EmoteSource {
+                            key: format!("emote/ffz/{}", emote.id),
+                            // Older emotes have protocol relative urls
+                            url: if url.starts_with("//") {
+                                format!("https:{url}")
+                            } else {
+                                url.clone()
+                            },
+                            name: emote.name,
+                        })
+                    })
+                    .collect()
+            }
+        }
+
+        #[derive(Deserialize)]
+        pub struct Emote {
+            pub id: u64,
+            pub name: String,
+            /// Image urls by scale, `1`, `2` and `4`
+            pub urls: HashMap<String, String>,
         }
     }
 }
@@ -0,0 +1,57 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre;
+use tokio::sync::broadcast;
+
+use crate::{
+    config::Config,
+    event::{Message, MessageKind},
+};
+
+/// Plays `mention_sound` for every chat message containing one of the mention keywords
+pub async fn watch_mentions(cfg: Config, mut messages: broadcast::Receiver<Message>) {
+    let Some(path) = cfg.mention_sound.clone().map(PathBuf::from) else {
+        return;
+    };
+    let finders = cfg.mention_finders();
+    loop {
+        match messages.recv().await {
+            Ok(message) => {
+                let text = message.msg.to_lowercase();
+                if message.kind == MessageKind::Chat
+                    && !message.username.eq_ignore_ascii_case(&cfg.username)
+                    && finders
+                        .iter()
+                        .any(|finder| finder.find(text.as_bytes()).is_some())
+                {
+                    let path = path.clone();
+                    // The output stream isn't `Send`, so it lives on a blocking thread
+                    tokio::task::spawn_blocking(move || {
+                        if let Err(e) = play(&path) {
+                            tracing::warn!(
+                                error = %e,
+                                path = %path.display(),
+                                "could not play mention sound"
+                            );
+                        }
+                    });
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Plays a `.wav` or `.ogg` file on the default output device until it ends
+fn play(path: &Path) -> eyre::Result<()> {
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&handle)?;
+    sink.append(rodio::Decoder::new(BufReader::new(File::open(path)?))?);
+    sink.sleep_until_end();
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+use color_eyre::eyre::{self, bail, eyre};
+use ratatui::style::Color;
+
+use crate::config::ThemeConfig;
+
+/// Colors the chat is drawn with
+#[derive(Clone, Copy)]
+pub struct Theme {
+    /// Usernames of chatters without a Twitch color, `None` picks one from the username
+    pub username: Option<Color>,
+    pub mention_bg: Color,
+    pub row_alternate_bg: Color,
+    /// System messages, hype trains, subs and raids
+    pub system_event_fg: Color,
+    pub border: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            username: None,
+            mention_bg: Color::Red,
+            row_alternate_bg: Color::Black,
+            system_event_fg: Color::Yellow,
+            border: Color::Reset,
+        }
+    }
+}
+
+/// Built-in themes by name, with their username, mention, alternate row, system event and
+/// border colors
+const BUILT_IN: &[(&str, [&str; 5])] = &[
+    (
+        "catppuccin-mocha",
+        ["#89b4fa", "#f38ba8", "#181825", "#f9e2af", "#b4befe"],
+    ),
+    (
+        "nord",
+        ["#88c0d0", "#bf616a", "#3b4252", "#ebcb8b", "#4c566a"],
+    ),
+    (
+        "gruvbox-dark",
+        ["#83a598", "#cc241d", "#32302f", "#fabd2f", "#665c54"],
+    ),
+];
+
+impl Theme {
+    /// The `name`d built-in theme, or the default one, with the `[theme]` colors on top
+    pub fn from_config(cfg: &ThemeConfig) -> eyre::Result<Self> {
+        let mut theme = match &cfg.name {
+            Some(name) => {
+                let Some((_, [username, mention, row, system, border])) =
+                    BUILT_IN.iter().find(|(theme, _)| theme == name)
+                else {
+                    let known = BUILT_IN
+                        .iter()
+                        .map(|(theme, _)| *theme)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    bail!("unknown theme `{name}`, expected one of: {known}");
+                };
+                Self {
+                    username: Some(parse_color("username_color", username)?),
+                    mention_bg: parse_color("mention_bg", mention)?,
+                    row_alternate_bg: parse_color("row_alternate_bg", row)?,
+                    system_event_fg: parse_color("system_event_fg", system)?,
+                    border: parse_color("border_color", border)?,
+                }
+            }
+            None => Self::default(),
+        };
+
+        if let Some(color) = &cfg.username_color {
+            theme.username = Some(parse_color("username_color", color)?);
+        }
+        for (name, color, field) in [
+            ("mention_bg", &cfg.mention_bg, &mut theme.mention_bg),
+            (
+                "row_alternate_bg",
+                &cfg.row_alternate_bg,
+                &mut theme.row_alternate_bg,
+            ),
+            (
+                "system_event_fg",
+                &cfg.system_event_fg,
+                &mut theme.system_event_fg,
+            ),
+            ("border_color", &cfg.border_color, &mut theme.border),
+        ] {
+            if let Some(color) = color {
+                *field = parse_color(name, color)?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// Accepts `#rrggbb` and names like `red` or `light-blue`
+fn parse_color(field: &str, color: &str) -> eyre::Result<Color> {
+    Color::from_str(color).map_err(|_| eyre!("[theme] {field}: unknown color `{color}`"))
+}
@@ -1,185 +1,3197 @@
-use std::{collections::VecDeque, io::Stdout};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    io::Stdout,
+    num::NonZero,
+    path::Path,
+    time::{Duration, Instant},
+};
 
+use chrono::{DateTime, Local};
 use color_eyre::config::HookBuilder;
 use color_eyre::eyre;
 use crossterm::{
-    event::{KeyCode, KeyEvent},
+    cursor::SetCursorStyle,
+    event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     terminal,
 };
 use indexmap::IndexMap;
+use lru::LruCache;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style, Stylize},
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListDirection},
+    widgets::{
+        Block, Borders, Clear, Gauge, List, ListDirection, ListState, Paragraph, Row, Table,
+        Widget, Wrap,
+    },
     Frame, Terminal,
 };
+use ratatui_image::{picker::Picker, StatefulImage};
+use regex::Regex;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::{select, sync::mpsc};
 use tui_textarea::TextArea;
 
-use crate::config::Config;
-use crate::event::{ev, EventHandler, Message};
+use crate::config::{Config, ConfigData, CursorStyle, RELATIVE_TIMESTAMPS};
+use crate::emoji_map;
+use crate::emote::Emotes;
+use crate::emote_tokenizer;
+use crate::event::{
+    ev, CharityCampaign, CompletedPrediction, ContributionSize, EventHandler, Message, MessageKind,
+    ReloadedConfig, StreamInfo, TaskState,
+};
+use crate::keybindings::{self, Action, ActionKey};
+use crate::persist;
+use crate::request::{
+    Cache, CacheStats, CustomReward, DropCampaign, LiveStream, ScheduleSegment, StreamStatus,
+};
+use crate::theme::Theme;
+
+/// Rendered line along with the message it was made from
+pub struct ChatLine<'a> {
+    received: Instant,
+    /// `None` for spacing lines
+    message: Option<Message>,
+    line: Line<'a>,
+    /// Emote names and the column they are drawn at
+    emotes: Vec<(u16, String)>,
+    /// Message uses a known emote, drawable or not
+    has_emote: bool,
+    /// Removed by a ban or timeout
+    deleted: bool,
+}
+
+/// Chat modes of a channel
+#[derive(Default)]
+struct RoomState {
+    /// Seconds between messages
+    slow: Option<u64>,
+    sub_only: bool,
+    emote_only: bool,
+    /// Minutes followed, `Some(0)` for any follower
+    followers_only: Option<u64>,
+}
+
+/// Which lines of a chat are listed
+#[derive(Clone, Copy)]
+pub struct LineFilter<'q> {
+    /// Only messages using an emote
+    emote_only: bool,
+    /// Only messages containing this lowercase text, which is highlighted
+    query: Option<&'q str>,
+}
+
+impl LineFilter<'_> {
+    fn is_active(&self) -> bool {
+        self.emote_only || self.query.is_some()
+    }
+
+    fn matches(&self, line: &ChatLine) -> bool {
+        if self.emote_only && !line.has_emote {
+            return false;
+        }
+        match self.query {
+            Some(query) => line
+                .message
+                .as_ref()
+                .is_some_and(|message| message.msg.to_lowercase().contains(query)),
+            None => true,
+        }
+    }
+}
+
+/// Highlights every occurrence of the lowercase `query` inside the spans of `line`
+fn highlight_matches<'a>(line: Line<'a>, query: &str) -> Line<'a> {
+    if query.is_empty() {
+        return line;
+    }
+
+    let mut spans = Vec::with_capacity(line.spans.len());
+    for span in line.spans {
+        let lower = span.content.to_lowercase();
+        // Byte offsets only line up when lowercasing keeps the length
+        if lower.len() != span.content.len() || !lower.contains(query) {
+            spans.push(span);
+            continue;
+        }
+
+        let mut start = 0;
+        for (offset, _) in lower.match_indices(query) {
+            if start < offset {
+                spans.push(Span::styled(
+                    span.content[start..offset].to_string(),
+                    span.style,
+                ));
+            }
+            let end = offset + query.len();
+            spans.push(Span::styled(
+                span.content[offset..end].to_string(),
+                span.style.black().on_yellow(),
+            ));
+            start = end;
+        }
+        if start < span.content.len() {
+            spans.push(Span::styled(span.content[start..].to_string(), span.style));
+        }
+    }
+    Line { spans, ..line }
+}
+
+impl ChatLine<'_> {
+    fn is_power_up(&self) -> bool {
+        self.message
+            .as_ref()
+            .is_some_and(|message| message.power_up)
+    }
+
+    /// Replaces the message text with `<message deleted>`
+    fn delete(&mut self) {
+        // Keep the timestamp and username in front of the text
+        if let Some(separator) = self.line.spans.iter().position(|span| span.content == ": ") {
+            self.line.spans.truncate(separator + 1);
+        }
+        self.line.spans.push(Span::raw("<message deleted>").dim());
+        self.emotes.clear();
+        self.has_emote = false;
+        self.deleted = true;
+    }
+}
+
+/// Colors power-up messages cycle through, one per animation frame
+const POWER_UP_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Yellow,
+    Color::Green,
+    Color::Cyan,
+    Color::Blue,
+    Color::Magenta,
+];
+const POWER_UP_FRAME: Duration = Duration::from_millis(100);
+/// Animated emotes advance at most this often, most frames last 20ms or more
+const ANIMATION_TICK: Duration = Duration::from_millis(20);
+
+/// Cells taken up by an inline emote
+const EMOTE_WIDTH: u16 = 2;
+
+pub struct Chat<'a> {
+    lines: VecDeque<ChatLine<'a>>,
+    bg_darken: bool,
+    alternating_rows: bool,
+    schedule: Vec<ScheduleSegment>,
+    charity: Option<CharityCampaign>,
+    /// `None` until polled, `Some(None)` while offline
+    stream_status: Option<Option<StreamStatus>>,
+    /// Stream delay in seconds, `None` until known
+    delay: Option<u64>,
+    stream_info: Option<StreamInfo>,
+    /// `None` until the room state arrives
+    room_state: Option<RoomState>,
+    /// Separate messages with a blank line
+    message_gap: bool,
+    max_message_display_length: Option<usize>,
+    /// strftime format of message timestamps, `None` hides them
+    timestamp_format: Option<String>,
+    /// Lowercase `highlight_keywords`
+    highlight_keywords: Vec<String>,
+    /// Index into `lines`, newest first
+    selected: Option<usize>,
+    /// Number of newest lines scrolled past
+    scroll: usize,
+    /// Messages received since scrolling away from the newest
+    unread: usize,
+    /// Messages received while another tab was active
+    unseen: usize,
+    /// Chatters by username, least recently active first
+    user_roster: IndexMap<String, Chatter>,
+    /// Offset being scrolled from and when, for smooth scrolling
+    scroll_animation: Option<(f32, Instant)>,
+    /// Emotes drawn in the last frame, only these are animated
+    visible_emotes: HashSet<String>,
+}
+
+/// Frame an animated emote is at, shared by all chats
+struct EmoteFrame {
+    index: usize,
+    shown_at: Instant,
+}
+
+/// Entry in a chat's user list
+struct Chatter {
+    last_seen: Instant,
+    role: ChatterRole,
+}
+
+#[derive(Clone, Copy)]
+enum ChatterRole {
+    Moderator,
+    Vip,
+    Viewer,
+}
+
+impl ChatterRole {
+    fn from_badges(badges: &[String]) -> Self {
+        let has = |name: &str| badges.iter().any(|badge| badge == name);
+        if has("moderator") || has("broadcaster") {
+            Self::Moderator
+        } else if has("vip") {
+            Self::Vip
+        } else {
+            Self::Viewer
+        }
+    }
+
+    /// IRC style prefix, `@` for moderators and `+` for VIPs
+    fn styled(self, username: &str) -> Line<'static> {
+        match self {
+            Self::Moderator => Line::from(format!("@{username}")).green(),
+            Self::Vip => Line::from(format!("+{username}")).magenta(),
+            Self::Viewer => Line::from(format!(" {username}")),
+        }
+    }
+}
+
+/// Chatters are offered for `@` completion this long after their last message
+const COMPLETION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Smooth scrolling takes 4 frames at 60Hz
+const SCROLL_FRAME: Duration = Duration::from_micros(16_667);
+const SCROLL_FRAMES: u32 = 4;
+
+impl<'a> Chat<'a> {
+    pub fn new(cfg: &ConfigData) -> Self {
+        let mut chat = Self {
+            lines: VecDeque::with_capacity(100),
+            bg_darken: false,
+            alternating_rows: false,
+            schedule: Vec::new(),
+            charity: None,
+            stream_status: None,
+            delay: None,
+            stream_info: None,
+            room_state: None,
+            message_gap: false,
+            max_message_display_length: None,
+            timestamp_format: None,
+            highlight_keywords: Vec::new(),
+            selected: None,
+            scroll: 0,
+            unread: 0,
+            unseen: 0,
+            user_roster: IndexMap::default(),
+            scroll_animation: None,
+            visible_emotes: HashSet::new(),
+        };
+        chat.apply_config(cfg);
+        chat
+    }
+
+    /// Takes the chat settings from `cfg`, on creation and when the config is reloaded.
+    /// Lines already pushed keep their formatting
+    pub fn apply_config(&mut self, cfg: &ConfigData) {
+        self.alternating_rows = cfg.alternating_rows;
+        self.message_gap = cfg.message_gap;
+        self.max_message_display_length = cfg.max_message_display_length;
+        self.timestamp_format = cfg.show_timestamps.then(|| cfg.timestamp_format.clone());
+        self.highlight_keywords = cfg
+            .highlight_keywords
+            .iter()
+            .map(|keyword| keyword.to_lowercase())
+            .collect();
+    }
+
+    /// `highlights` are only checked when the message isn't a mention
+    pub fn push_message(
+        &mut self,
+        mention_finders: &[memchr::memmem::Finder],
+        highlights: &[(Regex, Color)],
+        theme: &Theme,
+        emotes: &Emotes,
+        message: Message,
+    ) {
+        let text = message.msg.to_lowercase();
+        let found_mention = message.kind == MessageKind::Chat
+            && (mention_finders
+                .iter()
+                .any(|finder| finder.find(text.as_bytes()).is_some())
+                || self.has_highlight_keyword(&message.msg));
+        let (line, line_emotes) =
+            self.format_message(&message, self.max_message_display_length, theme, emotes);
+        let has_emote = emote_tokenizer::tokenize(&message.msg, |word| emotes.is_known(word))
+            .iter()
+            .any(|token| matches!(token, emote_tokenizer::Token::Emote(_)));
+        let highlight = highlights
+            .iter()
+            .filter(|_| message.kind == MessageKind::Chat)
+            .find(|(regex, _)| regex.is_match(&message.msg));
+        let line = line.bg({
+            if found_mention {
+                theme.mention_bg
+            } else if let Some((_, color)) = highlight {
+                *color
+            } else if self.alternating_rows && self.bg_darken {
+                theme.row_alternate_bg
+            } else {
+                Color::Reset
+            }
+        });
+
+        self.bg_darken = !self.bg_darken;
+
+        if self.message_gap && !self.lines.is_empty() {
+            self.push_line(None, Line::default(), Vec::new(), false);
+        }
+        self.push_line(Some(message), line, line_emotes, has_emote);
+    }
+
+    /// Moves `username` to the end of the roster and drops chatters idle for longer than `ttl`,
+    /// or than `@` completion offers them
+    fn saw_chatter(&mut self, username: &str, badges: &[String], ttl: Duration) {
+        let ttl = ttl.max(COMPLETION_TTL);
+        self.user_roster.shift_remove(username);
+        self.user_roster.insert(
+            username.to_string(),
+            Chatter {
+                last_seen: Instant::now(),
+                role: ChatterRole::from_badges(badges),
+            },
+        );
+        while self
+            .user_roster
+            .first()
+            .is_some_and(|(_, chatter)| chatter.last_seen.elapsed() > ttl)
+        {
+            self.user_roster.shift_remove_index(0);
+        }
+    }
+
+    /// Chatters active within `ttl`, most recently active first
+    fn chatters(&self, ttl: Duration) -> impl Iterator<Item = (&String, &Chatter)> {
+        self.user_roster
+            .iter()
+            .rev()
+            .take_while(move |(_, chatter)| chatter.last_seen.elapsed() <= ttl)
+    }
+
+    /// Most recently active first
+    fn user_list(&self, ttl: Duration) -> Vec<Line<'static>> {
+        self.chatters(ttl)
+            .map(|(username, chatter)| chatter.role.styled(username))
+            .collect()
+    }
+
+    fn has_highlight_keyword(&self, text: &str) -> bool {
+        if self.highlight_keywords.is_empty() {
+            return false;
+        }
+        let text = text.to_lowercase();
+        self.highlight_keywords
+            .iter()
+            .any(|keyword| text.contains(keyword.as_str()))
+    }
+
+    /// Message text is cut off after `max_len` characters, emotes are left blank to be drawn
+    /// over as images
+    fn format_message(
+        &mut self,
+        message: &Message,
+        max_len: Option<usize>,
+        theme: &Theme,
+        emotes: &Emotes,
+    ) -> (Line<'a>, Vec<(u16, String)>) {
+        let username_style = match message.kind {
+            MessageKind::Chat => Style::default().fg(username_color(message, theme)),
+            MessageKind::System | MessageKind::UserNotice | MessageKind::HypeTrain(_) => {
+                Style::default().fg(theme.system_event_fg)
+            }
+        };
+        let text_style = match message.kind {
+            MessageKind::HypeTrain(ContributionSize::Medium) => Style::default().yellow(),
+            MessageKind::HypeTrain(ContributionSize::Large) => {
+                Style::default().fg(Color::Rgb(255, 215, 0)).bold()
+            }
+            _ => Style::default(),
+        };
+
+        if message.kind == MessageKind::UserNotice {
+            return (
+                Line::raw(message.msg.clone())
+                    .fg(theme.system_event_fg)
+                    .centered(),
+                Vec::new(),
+            );
+        }
+
+        let mut text = message.msg.clone();
+        let truncated = match max_len.and_then(|max_len| text.char_indices().nth(max_len)) {
+            Some((cut, _)) => {
+                text.truncate(cut);
+                true
+            }
+            None => false,
+        };
+
+        let mut spans = Vec::new();
+        match self.timestamp_format.as_deref() {
+            Some(RELATIVE_TIMESTAMPS) => {
+                spans.push(Span::raw(relative_timestamp(message.timestamp)).dim());
+            }
+            Some(format) => {
+                spans.push(Span::raw(format!("[{}] ", message.timestamp.format(format))).dim());
+            }
+            None => {}
+        }
+        if let Some(account) = &message.account {
+            spans.push(Span::raw(format!("[{account}] ")).cyan().dim());
+        }
+        spans.push(Span::styled(message.username.clone(), username_style));
+        spans.push(Span::styled(": ", Style::default()));
+        let mut line_emotes = Vec::new();
+        let mut col = spans.iter().map(|s| s.width() as u16).sum::<u16>();
+        let tokens = emote_tokenizer::tokenize(&text, |word| {
+            emotes.contains(word) || emoji_map::emoji(word).is_some()
+        });
+        for token in tokens {
+            let span = match token {
+                emote_tokenizer::Token::Emote(name) if emotes.contains(name) => {
+                    line_emotes.push((col, name.to_string()));
+                    Span::raw(" ".repeat(EMOTE_WIDTH as usize))
+                }
+                // Fall back to emoji when the emote can't be drawn
+                emote_tokenizer::Token::Emote(name) => {
+                    Span::raw(emoji_map::emoji(name).unwrap_or(name).to_string())
+                }
+                emote_tokenizer::Token::Text(text) => Span::raw(text.to_string()),
+            };
+            col += span.width() as u16;
+            spans.push(span);
+        }
+
+        if truncated {
+            spans.push(Span::raw("…"));
+        }
+
+        (Line::from(spans).style(text_style), line_emotes)
+    }
+
+    /// Shows the full text of the selected message
+    pub fn expand_selected(&mut self, theme: &Theme, emotes: &Emotes) {
+        let Some(index) = self.selected else {
+            return;
+        };
+        let Some(message) = self
+            .lines
+            .get(index)
+            .filter(|l| !l.deleted)
+            .and_then(|l| l.message.clone())
+        else {
+            return;
+        };
+
+        let (line, line_emotes) = self.format_message(&message, None, theme, emotes);
+        self.lines[index].line = line.style(self.lines[index].line.style);
+        self.lines[index].emotes = line_emotes;
+    }
+
+    /// Replaces the text of every chat message from `user` with `<message deleted>`
+    pub fn delete_messages_from(&mut self, user: &str) {
+        for line in &mut self.lines {
+            if !line.deleted
+                && line.message.as_ref().is_some_and(|message| {
+                    message.kind == MessageKind::Chat && message.username.eq_ignore_ascii_case(user)
+                })
+            {
+                line.delete();
+            }
+        }
+    }
+
+    /// Returns whether a line with the Twitch message id `id` was found
+    pub fn delete_message(&mut self, id: &str) -> bool {
+        let line = self.lines.iter_mut().find(|line| {
+            line.message
+                .as_ref()
+                .is_some_and(|message| message.id.as_deref() == Some(id))
+        });
+        match line {
+            Some(line) if !line.deleted => {
+                line.delete();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Draws emote images over the blank space left for them in the visible lines
+    fn render_emotes(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        offset: usize,
+        filter: LineFilter,
+        emotes: &mut Emotes,
+        emote_frames: &HashMap<String, EmoteFrame>,
+    ) {
+        self.visible_emotes.clear();
+        let lines = self.lines.iter().filter(|line| filter.matches(line));
+        for (i, line) in lines.skip(offset).enumerate() {
+            let Some(y) = (area.bottom() - 1)
+                .checked_sub(i as u16)
+                .filter(|y| *y >= area.y)
+            else {
+                break;
+            };
+
+            for (col, name) in &line.emotes {
+                let x = area.x + col;
+                if x + EMOTE_WIDTH > area.right() {
+                    break;
+                }
+                let index = emote_frames.get(name).map_or(0, |frame| frame.index);
+                if let Some(state) = emotes.protocol(name, index) {
+                    self.visible_emotes.insert(name.clone());
+                    frame.render_stateful_widget(
+                        StatefulImage::new(None),
+                        Rect::new(x, y, EMOTE_WIDTH, 1),
+                        state,
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn select_older(&mut self) {
+        let newest = self.lines.len().saturating_sub(1);
+        self.selected = Some(self.selected.map_or(0, |i| (i + 1).min(newest)));
+    }
+
+    pub fn select_newer(&mut self) {
+        self.selected = self.selected.and_then(|i| i.checked_sub(1));
+    }
+
+    /// Scrolls towards older lines by `delta`, negative scrolls towards newer ones. Clears the
+    /// selection, which would otherwise keep the list scrolled to it
+    pub fn scroll_by(&mut self, delta: isize, animate: bool) {
+        self.selected = None;
+        let target = self
+            .scroll
+            .saturating_add_signed(delta)
+            .min(self.lines.len().saturating_sub(1));
+        if animate {
+            self.scroll_animation = Some((self.scroll_offset(), Instant::now()));
+        }
+        self.scroll = target;
+        if target == 0 {
+            self.unread = 0;
+        }
+    }
+
+    /// Current scroll position, eased out between the old and new offset while animating
+    fn scroll_offset(&self) -> f32 {
+        let Some((from, started)) = self.scroll_animation else {
+            return self.scroll as f32;
+        };
+
+        let t = (started.elapsed().as_secs_f32() / (SCROLL_FRAME * SCROLL_FRAMES).as_secs_f32())
+            .min(1.0);
+        let eased = 1.0 - (1.0 - t).powi(3);
+        from + (self.scroll as f32 - from) * eased
+    }
+
+    fn push_line(
+        &mut self,
+        message: Option<Message>,
+        line: Line<'a>,
+        emotes: Vec<(u16, String)>,
+        has_emote: bool,
+    ) {
+        let line = ChatLine {
+            received: Instant::now(),
+            message,
+            line,
+            emotes,
+            has_emote,
+            deleted: false,
+        };
+        // Keep the selection and scroll position on the same line as new ones come in
+        if let Some(selected) = &mut self.selected {
+            *selected = (*selected + 1).min(self.lines.capacity() - 1);
+        }
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.lines.capacity() - 1);
+            if line.message.is_some() {
+                self.unread += 1;
+            }
+        }
+        if self.lines.len() == self.lines.capacity() {
+            self.lines.pop_back();
+            self.lines.push_front(line);
+        } else {
+            self.lines.push_front(line);
+        }
+    }
+
+    /// Removes lines received longer than `max_age` ago
+    pub fn prune(&mut self, max_age: Duration) {
+        // Newest lines are at the front
+        while self
+            .lines
+            .back()
+            .is_some_and(|line| line.received.elapsed() > max_age)
+        {
+            self.lines.pop_back();
+        }
+    }
+
+    pub fn schedule_table(&self) -> Table<'a> {
+        let rows = self.schedule.iter().map(|segment| {
+            let minutes = segment.duration.num_minutes();
+            Row::new(vec![
+                segment.start.format("%a %d %b %H:%M").to_string(),
+                format!("{}h {:02}m", minutes / 60, minutes % 60),
+                match &segment.category {
+                    Some(category) => format!("{} ({category})", segment.title),
+                    None => segment.title.clone(),
+                },
+            ])
+        });
+
+        Table::new(
+            rows,
+            [
+                Constraint::Length(16),
+                Constraint::Length(7),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(vec!["Start", "Length", "Title"]).bold())
+        .block(
+            Block::bordered()
+                .title("Schedule")
+                .title_alignment(Alignment::Center),
+        )
+    }
+
+    pub fn charity_gauge(&self) -> Option<Gauge<'a>> {
+        let campaign = self.charity.as_ref()?;
+        let ratio = if campaign.target > 0.0 {
+            (campaign.current / campaign.target).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        Some(
+            Gauge::default()
+                .block(
+                    Block::bordered()
+                        .title(format!("Charity: {}", campaign.charity))
+                        .title_alignment(Alignment::Center),
+                )
+                .gauge_style(Style::default().magenta())
+                .ratio(ratio)
+                .label(format!(
+                    "{:.2} / {:.2} {}",
+                    campaign.current, campaign.target, campaign.currency
+                )),
+        )
+    }
+
+    /// Active chat modes shown next to the channel name, `[-]` when there are none
+    fn room_state_label(&self) -> Option<String> {
+        let state = self.room_state.as_ref()?;
+        let mut badges = Vec::new();
+        if let Some(secs) = state.slow {
+            badges.push(format!("[slow:{secs}s]"));
+        }
+        if state.sub_only {
+            badges.push("[sub-only]".into());
+        }
+        if state.emote_only {
+            badges.push("[emote-only]".into());
+        }
+        match state.followers_only {
+            Some(0) => badges.push("[followers]".into()),
+            Some(minutes) => badges.push(format!("[followers ≥{minutes}min]")),
+            None => {}
+        }
+        if badges.is_empty() {
+            badges.push("[-]".into());
+        }
+        Some(badges.join(" "))
+    }
+
+    /// `animation_frame` color cycles power-up messages, `None` leaves them static
+    pub fn list(
+        &self,
+        title: String,
+        animation_frame: Option<usize>,
+        filter: LineFilter,
+    ) -> List<'a> {
+        let relative = self.timestamp_format.as_deref() == Some(RELATIVE_TIMESTAMPS);
+        let lines = self.lines.iter().filter(move |line| filter.matches(line));
+        List::new(lines.map(move |line| {
+            let mut text = line.line.clone();
+            // Relative timestamps are brought up to date on every draw
+            if let Some(message) = line
+                .message
+                .as_ref()
+                .filter(|message| relative && message.kind != MessageKind::UserNotice)
+            {
+                if let Some(span) = text.spans.first_mut() {
+                    span.content = relative_timestamp(message.timestamp).into();
+                }
+            }
+            let text = match animation_frame.filter(|_| line.is_power_up()) {
+                Some(frame) => text
+                    .fg(POWER_UP_COLORS[frame % POWER_UP_COLORS.len()])
+                    .bold(),
+                None => text,
+            };
+            match filter.query {
+                Some(query) => highlight_matches(text, query),
+                None => text,
+            }
+        }))
+        .direction(ListDirection::BottomToTop)
+        .block(
+            Block::bordered()
+                .title(title)
+                .title_alignment(Alignment::Center),
+        )
+    }
+}
+
+/// Always 6 columns wide, so emotes drawn after it stay in place as it grows
+fn relative_timestamp(timestamp: DateTime<Local>) -> String {
+    let seconds = (Local::now() - timestamp).num_seconds().max(0);
+    let age = match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86400 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    };
+    format!("[{age:>3}] ")
+}
+
+const RAID_POPUP_DURATION: Duration = Duration::from_secs(10);
+
+/// Tab collecting every whisper
+const WHISPERS_TAB: &str = "[whispers]";
+
+/// Lines scrolled per mouse wheel notch
+const MOUSE_SCROLL_LINES: isize = 3;
+const WHISPER_ALERT_DURATION: Duration = Duration::from_secs(5);
+const WHISPER_FLASH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Seconds a user is timed out for from the context menu
+const TIMEOUT_DURATION: u32 = 600;
+
+/// Actions for the user who sent the selected message
+struct ContextMenu {
+    channel: String,
+    username: String,
+}
+
+impl ContextMenu {
+    const OPTIONS: [&'static str; 7] = [
+        "[C] Copy username",
+        "[F] Follow/Unfollow on twitch.tv",
+        "[B] Ban",
+        "[T] Timeout",
+        "[I] Ignore",
+        "[V] View profile",
+        "[E] Expand message",
+    ];
+}
+
+type ConfirmAction = Box<dyn FnOnce(&mut State<'_>)>;
+type PickerAction = Box<dyn FnOnce(&mut State<'_>, String)>;
+
+/// Overlay that takes every key until it's closed
+enum Modal {
+    /// Closed by any key
+    Alert(String),
+    /// `action` runs on `y` or Enter
+    Confirm {
+        message: String,
+        action: ConfirmAction,
+    },
+    /// Typing filters `items`, Enter runs `action` with the selected one
+    Picker {
+        title: String,
+        items: Vec<String>,
+        query: String,
+        selected: usize,
+        action: PickerAction,
+    },
+}
+
+/// Rows a picker shows at most
+const PICKER_HEIGHT: u16 = 12;
+
+/// Mystery gifts larger than this get a progress tracker
+const GIFT_BOMB_MIN: usize = 5;
+const GIFT_BOMB_WINDOW: Duration = Duration::from_secs(10);
+
+/// Subs handed out so far from a large mystery gift
+struct GiftBomb {
+    gifter: String,
+    received: usize,
+    total: usize,
+    /// Tracking stops here even if not every sub arrived
+    deadline: Instant,
+}
+
+/// Number of sent messages remembered across sessions
+const MESSAGE_HISTORY_LEN: usize = 50;
+
+fn textarea_with<'a>(text: &str) -> TextArea<'a> {
+    let mut textarea = TextArea::from(text.lines());
+    textarea.set_block(Block::default().borders(Borders::ALL));
+    textarea.move_cursor(tui_textarea::CursorMove::End);
+    textarea
+}
+
+/// Where each tab name lands on the top border of `area`, laid out like the block lays out
+/// centered titles: one space apart
+fn tab_title_areas(area: Rect, titles: Vec<(String, u16)>) -> Vec<(String, Rect)> {
+    let total = titles
+        .iter()
+        .map(|(_, width)| width + 1)
+        .sum::<u16>()
+        .saturating_sub(1);
+    let left = area.x + 1;
+    let right = area.right().saturating_sub(1);
+    let mut x = left + (right.saturating_sub(left)).saturating_sub(total) / 2;
+    titles
+        .into_iter()
+        .map(|(name, width)| {
+            let title = Rect {
+                x,
+                y: area.y,
+                width: width.min(right.saturating_sub(x)),
+                height: 1,
+            };
+            x = x.saturating_add(width + 1);
+            (name, title)
+        })
+        .collect()
+}
+
+/// Picker items containing `query`, ignoring case
+fn picker_matches<'b>(items: &'b [String], query: &'b str) -> impl Iterator<Item = &'b String> {
+    let query = query.to_lowercase();
+    items
+        .iter()
+        .filter(move |item| item.to_lowercase().contains(&query))
+}
+
+/// Fails on the first invalid pattern, so a typo is reported instead of never matching
+fn compile_highlights(cfg: &ConfigData) -> eyre::Result<Vec<(Regex, Color)>> {
+    cfg.highlights
+        .iter()
+        .map(|rule| {
+            let regex = Regex::new(&rule.pattern)
+                .map_err(|e| eyre::eyre!("invalid highlight pattern {:?}: {e}", rule.pattern))?;
+            let (r, g, b) = rule.color;
+            Ok((regex, Color::Rgb(r, g, b)))
+        })
+        .collect()
+}
+
+/// Twitch's default username colors, for chatters who never picked one
+const USERNAME_COLORS: [Color; 12] = [
+    Color::Rgb(255, 0, 0),
+    Color::Rgb(0, 0, 255),
+    Color::Rgb(0, 128, 0),
+    Color::Rgb(178, 34, 34),
+    Color::Rgb(255, 127, 80),
+    Color::Rgb(154, 205, 50),
+    Color::Rgb(255, 69, 0),
+    Color::Rgb(46, 139, 87),
+    Color::Rgb(218, 165, 32),
+    Color::Rgb(210, 105, 30),
+    Color::Rgb(95, 158, 160),
+    Color::Rgb(30, 144, 255),
+];
+
+/// The sender's chosen color, or one picked from their name so it stays the same
+fn username_color(message: &Message, theme: &Theme) -> Color {
+    use std::hash::{Hash, Hasher};
+
+    if let Some((r, g, b)) = message.color {
+        return Color::Rgb(r, g, b);
+    }
+    if let Some(color) = theme.username {
+        return color;
+    }
+    let mut hasher = std::hash::DefaultHasher::new();
+    message.username.hash(&mut hasher);
+    USERNAME_COLORS[hasher.finish() as usize % USERNAME_COLORS.len()]
+}
+
+/// Ad break lengths Twitch accepts, the first is used when `/commercial` isn't given one
+const COMMERCIAL_LENGTHS: [u32; 6] = [30, 60, 90, 120, 150, 180];
+
+/// Seconds a poll runs for when `/poll` isn't given a duration
+const POLL_DURATION: u32 = 60;
+
+/// Parses `"Question" "Option A" "Option B" [duration_seconds]` into a title, choices and
+/// duration, Twitch wants 2 to 5 choices
+fn parse_poll(args: &str) -> Option<(String, Vec<String>, u32)> {
+    let mut quoted = Vec::new();
+    let mut rest = args.trim();
+    while let Some(stripped) = rest.strip_prefix('"') {
+        let (text, after) = stripped.split_once('"')?;
+        quoted.push(text.to_string());
+        rest = after.trim_start();
+    }
+    let duration = match rest {
+        "" => POLL_DURATION,
+        seconds => seconds.parse().ok()?,
+    };
+
+    let (title, choices) = quoted.split_first()?;
+    (2..=5)
+        .contains(&choices.len())
+        .then(|| (title.clone(), choices.to_vec(), duration))
+}
+
+/// Emote names completing the `:name` word before the cursor
+struct Autocomplete {
+    candidates: Vec<String>,
+    selected: usize,
+}
+
+/// `@name` completion cycled through by repeated Tab presses
+struct UsernameCompletion {
+    candidates: Vec<String>,
+    index: usize,
+}
+
+/// Characters typed after `:` before emote names are suggested
+const AUTOCOMPLETE_MIN_LEN: usize = 2;
+const AUTOCOMPLETE_LIMIT: usize = 8;
+
+/// What keys do, Vim style
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// Keys navigate chat and tabs
+    Normal,
+    /// Keys type into the message input
+    Insert,
+    /// Typing a `:` command into the message input
+    Command,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::Insert => "INSERT",
+            Self::Command => "COMMAND",
+        }
+    }
+}
+
+/// Row above the message input with the active channel, chat connection and mode
+struct StatusBar<'a> {
+    channel: Option<&'a str>,
+    /// State of the main chat connection, `None` until it's started
+    connection: Option<&'a TaskState>,
+    mode: Mode,
+    /// Slow mode delay of the active channel in seconds
+    slow: Option<u64>,
+    /// Other tabs with messages that arrived while they weren't active
+    unread: Vec<(&'a str, usize)>,
+}
+
+impl Widget for StatusBar<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut spans = vec![Span::raw(format!(" {} ", self.mode.name()))
+            .reversed()
+            .bold()];
+        if let Some(channel) = self.channel {
+            spans.push(Span::raw(format!(" {channel}")).bold());
+        }
+        spans.push(Span::raw(" "));
+        spans.push(match self.connection {
+            Some(TaskState::Running) => Span::raw("connected").green(),
+            Some(TaskState::Retrying) => Span::raw("reconnecting").yellow(),
+            Some(TaskState::Finished | TaskState::Failed(_)) => Span::raw("disconnected").red(),
+            None => Span::raw("connecting").dim(),
+        });
+        if let Some(secs) = self.slow {
+            spans.push(Span::raw(format!(" [slow:{secs}s]")).yellow());
+        }
+        for (channel, count) in self.unread {
+            spans.push(Span::raw(format!(" {channel}:{count}")).dim());
+        }
+        Line::from(spans).render(area, buf);
+    }
+}
+
+/// Search across every tab's buffer
+#[derive(Default)]
+struct Search {
+    query: String,
+    selected: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SidePanel {
+    Schedule,
+    Leaderboard,
+    Streams,
+    Drops,
+    Predictions,
+    Rewards,
+}
+
+/// Completed predictions kept for the history panel
+const PREDICTION_HISTORY_LEN: usize = 10;
+
+#[allow(unused)]
+struct State<'a> {
+    tabs: IndexMap<String, Chat<'a>>,
+    /// Tab names on screen as of the last draw, for mouse clicks
+    tab_title_areas: Vec<(String, Rect)>,
+    mode: Mode,
+    keymap: HashMap<ActionKey, Action>,
+    /// Extra account messages are sent as, `None` for the main account
+    active_account: Option<String>,
+    /// `g` was pressed in normal mode, a second one jumps to the top
+    pending_g: bool,
+    active_tab: Option<String>,
+    textarea: TextArea<'a>,
+    /// Unsent textarea contents per channel
+    drafts: IndexMap<String, String>,
+    /// Sent messages, newest first
+    message_history: VecDeque<String>,
+    /// Position in `message_history` while recalling with Up/Down
+    history_index: Option<usize>,
+    side_panel: Option<SidePanel>,
+    /// Subs gifted per user this session
+    gift_totals: HashMap<String, usize>,
+    /// Channel to emote to times used, kept across sessions
+    emote_counts: HashMap<String, HashMap<String, usize>>,
+    /// EventSub subscriptions that failed the last health check
+    eventsub_degraded: usize,
+    search: Option<Search>,
+    /// Live streams per watched category, in config order
+    category_streams: IndexMap<String, Vec<LiveStream>>,
+    /// Index into every stream in `category_streams`
+    selected_stream: usize,
+    drop_campaigns: Vec<DropCampaign>,
+    /// Why the last drops fetch failed
+    drops_error: Option<String>,
+    /// Newest first
+    predictions: VecDeque<CompletedPrediction>,
+    selected_drop: usize,
+    /// Custom rewards of the active channel
+    rewards: Vec<CustomReward>,
+    selected_reward: usize,
+    /// Campaign whose drops are listed under it
+    expanded_drop: Option<usize>,
+    seen_msg_ids: LruCache<String, ()>,
+    /// Incoming raid text and when it is dismissed
+    raid_popup: Option<(Instant, String)>,
+    gift_bomb: Option<GiftBomb>,
+    context_menu: Option<ContextMenu>,
+    modal: Option<Modal>,
+    /// Toast for the newest whisper and when it arrived
+    whisper_alert: Option<(Instant, String)>,
+    /// Id of the message the textarea is replying to
+    reply_to: Option<String>,
+    /// Users whose messages are dropped
+    ignored_users: HashSet<String>,
+    /// Ignored with `/ignore`, forgotten on exit unless moved to `ignored_users` by `:save-ignore`
+    session_ignored_users: HashSet<String>,
+    /// `/join` completions, shown in a popup above the textarea
+    channel_suggestions: Vec<String>,
+    suggestion_index: Option<usize>,
+    /// Emote completions, shown in a popup above the textarea
+    autocomplete: Option<Autocomplete>,
+    username_completion: Option<UsernameCompletion>,
+    /// Shows the raw IRC line and bytes of the selected message
+    debug_view: bool,
+    /// Only list messages that use an emote
+    emote_only_view: bool,
+    /// Active chat is filtered to messages containing this text while `Some`
+    buffer_search: Option<String>,
+    /// Background tasks by name, with their state and last activity
+    tasks: IndexMap<String, (TaskState, DateTime<Local>)>,
+    /// Shown under the tasks, fetched each time the panel opens
+    cache_stats: Option<CacheStats>,
+    task_panel: bool,
+    /// Two tabs side by side, keys and typing go to the focused one in `active_tab`
+    split_mode: bool,
+    /// Tab in the pane without focus
+    secondary_active_tab: Option<String>,
+    /// Whether the focused pane is the right one
+    focus_right: bool,
+    /// Sidebar listing every tab
+    channel_list: bool,
+    /// Panel listing recent chatters of the active tab
+    user_list: bool,
+    /// Rows scrolled past in the user list
+    user_list_scroll: usize,
+    /// Where the user list was drawn, for the mouse wheel
+    user_list_area: Option<Rect>,
+    /// Where the channel list was drawn and its scroll offset, for mouse clicks
+    channel_list_area: Option<(Rect, usize)>,
+    /// Power-up animations are timed from here
+    animation_start: Instant,
+    /// Power-up frame at the last tick, the ticker runs faster than power-ups animate
+    power_up_frame: Option<usize>,
+    /// Current frame of the animated emotes on screen
+    emote_frames: HashMap<String, EmoteFrame>,
+    quit: bool,
+    request_redraw: bool,
+    /// One per mention keyword
+    mention_finders: Vec<memchr::memmem::Finder<'a>>,
+    /// Compiled `highlights` rules
+    highlights: Vec<(Regex, Color)>,
+    theme: Theme,
+    emotes: Emotes,
+    handler_tx: UnboundedSender<ev::Send>,
+    /// Lets the ui schedule its own events, like animation frames
+    event_tx: UnboundedSender<ev::In>,
+    cfg: Config,
+}
+
+impl<'a> State<'a> {
+    fn new(
+        start_focused: bool,
+        mention_finders: Vec<memchr::memmem::Finder<'a>>,
+        emotes: Emotes,
+        handler_tx: UnboundedSender<ev::Send>,
+        event_tx: UnboundedSender<ev::In>,
+        cfg: Config,
+    ) -> Self {
+        let textarea = textarea_with("");
+        Self {
+            mode: if start_focused {
+                Mode::Insert
+            } else {
+                Mode::Normal
+            },
+            pending_g: false,
+            keymap: HashMap::new(),
+            active_account: None,
+            active_tab: None,
+            tabs: IndexMap::default(),
+            tab_title_areas: Vec::new(),
+            textarea,
+            drafts: IndexMap::default(),
+            message_history: VecDeque::with_capacity(MESSAGE_HISTORY_LEN),
+            history_index: None,
+            side_panel: None,
+            gift_totals: HashMap::new(),
+            emote_counts: HashMap::new(),
+            eventsub_degraded: 0,
+            raid_popup: None,
+            gift_bomb: None,
+            context_menu: None,
+            modal: None,
+            whisper_alert: None,
+            reply_to: None,
+            ignored_users: HashSet::new(),
+            session_ignored_users: HashSet::new(),
+            search: None,
+            category_streams: IndexMap::default(),
+            selected_stream: 0,
+            drop_campaigns: Vec::new(),
+            drops_error: None,
+            rewards: Vec::new(),
+            selected_reward: 0,
+            predictions: VecDeque::with_capacity(PREDICTION_HISTORY_LEN),
+            selected_drop: 0,
+            expanded_drop: None,
+            seen_msg_ids: LruCache::new(NonZero::new(500).unwrap()),
+            channel_suggestions: Vec::new(),
+            suggestion_index: None,
+            autocomplete: None,
+            username_completion: None,
+            debug_view: false,
+            emote_only_view: false,
+            buffer_search: None,
+            tasks: IndexMap::default(),
+            cache_stats: None,
+            task_panel: false,
+            split_mode: false,
+            secondary_active_tab: None,
+            focus_right: false,
+            channel_list: false,
+            user_list: false,
+            user_list_scroll: 0,
+            user_list_area: None,
+            channel_list_area: None,
+            animation_start: Instant::now(),
+            power_up_frame: None,
+            emote_frames: HashMap::new(),
+            quit: false,
+            mention_finders,
+            highlights: Vec::new(),
+            theme: Theme::default(),
+            emotes,
+            request_redraw: false,
+            handler_tx,
+            event_tx,
+            cfg,
+        }
+    }
+
+    fn key_event(&mut self, key: KeyEvent) {
+        if let Some(modal) = self.modal.take() {
+            self.modal_key_event(modal, key);
+            self.request_redraw = true;
+            return;
+        }
+
+        // Any key dismisses the raid popup
+        if self.raid_popup.take().is_some() {
+            self.request_redraw = true;
+            return;
+        }
+
+        if self.search.is_some() {
+            self.search_key_event(key);
+            self.request_redraw = true;
+            return;
+        }
+
+        if self.buffer_search.is_some() {
+            self.buffer_search_key_event(key);
+            self.request_redraw = true;
+            return;
+        }
+
+        if let Some(menu) = self.context_menu.take() {
+            self.context_menu_key_event(menu, key);
+            self.request_redraw = true;
+            return;
+        }
+
+        if self.mode != Mode::Normal && self.autocomplete_key_event(key) {
+            self.request_redraw = true;
+            return;
+        }
+
+        let pending_g = std::mem::take(&mut self.pending_g);
+
+        let action_key = ActionKey::from(key);
+        let action = self
+            .keymap
+            .get(&action_key)
+            .copied()
+            .filter(|_| self.mode == Mode::Normal || action_key.works_while_typing());
+        if let Some(action) = action {
+            self.run_action(action);
+        } else {
+            match key {
+                KeyEvent {
+                    code: KeyCode::Esc, ..
+                } => {
+                    if self.mode == Mode::Command {
+                        self.textarea = textarea_with("");
+                    }
+                    if self.mode != Mode::Normal {
+                        self.mode = Mode::Normal;
+                        self.history_index = None;
+                    } else {
+                        self.quit = true;
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Enter,
+                    ..
+                } => {
+                    if self.mode != Mode::Normal {
+                        self.submit();
+                        if self.mode == Mode::Command {
+                            self.mode = Mode::Normal;
+                        }
+                    } else if self.side_panel == Some(SidePanel::Streams) {
+                        self.join_selected_stream();
+                    } else if self.side_panel == Some(SidePanel::Drops) {
+                        self.expanded_drop = if self.expanded_drop == Some(self.selected_drop) {
+                            None
+                        } else {
+                            Some(self.selected_drop)
+                        };
+                    } else if let Some(message) = self
+                        .selected_message()
+                        .filter(|message| message.kind == MessageKind::Chat)
+                    {
+                        self.context_menu = Some(ContextMenu {
+                            channel: message.channel.clone(),
+                            username: message.username.clone(),
+                        });
+                    } else {
+                        self.expand_selected();
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Tab, ..
+                } if self.mode != Mode::Normal => {
+                    if !self.complete_username() {
+                        self.complete_join();
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Char(c @ '1'..='9'),
+                    modifiers: KeyModifiers::ALT,
+                    ..
+                } => self.switch_tab(c as usize - '1' as usize),
+                KeyEvent {
+                    code: KeyCode::Char('g'),
+                    modifiers: KeyModifiers::NONE,
+                    ..
+                } if self.mode == Mode::Normal => {
+                    if pending_g {
+                        self.scroll_active_chat(isize::MAX);
+                    } else {
+                        self.pending_g = true;
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                } if self.mode == Mode::Normal && self.side_panel == Some(SidePanel::Streams) => {
+                    self.selected_stream = self.selected_stream.saturating_sub(1);
+                }
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                } if self.mode == Mode::Normal && self.side_panel == Some(SidePanel::Streams) => {
+                    let count = self.category_streams.values().map(Vec::len).sum::<usize>();
+                    self.selected_stream = (self.selected_stream + 1).min(count.saturating_sub(1));
+                }
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                } if self.mode == Mode::Normal && self.side_panel == Some(SidePanel::Rewards) => {
+                    self.selected_reward = self.selected_reward.saturating_sub(1);
+                }
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                } if self.mode == Mode::Normal && self.side_panel == Some(SidePanel::Rewards) => {
+                    self.selected_reward =
+                        (self.selected_reward + 1).min(self.rewards.len().saturating_sub(1));
+                }
+                KeyEvent {
+                    code: KeyCode::Char(' '),
+                    ..
+                } if self.mode == Mode::Normal && self.side_panel == Some(SidePanel::Rewards) => {
+                    self.toggle_selected_reward();
+                }
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                } if self.mode == Mode::Normal && self.side_panel == Some(SidePanel::Drops) => {
+                    self.selected_drop = self.selected_drop.saturating_sub(1);
+                }
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                } if self.mode == Mode::Normal && self.side_panel == Some(SidePanel::Drops) => {
+                    self.selected_drop =
+                        (self.selected_drop + 1).min(self.drop_campaigns.len().saturating_sub(1));
+                }
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                } if self.mode == Mode::Normal => {
+                    if let Some(chat) = self.active_chat_mut() {
+                        chat.select_older();
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                } if self.mode == Mode::Normal => {
+                    if let Some(chat) = self.active_chat_mut() {
+                        chat.select_newer();
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                } if self.mode != Mode::Normal => {
+                    let index = self.history_index.map_or(0, |i| i + 1);
+                    if let Some(msg) = self.message_history.get(index) {
+                        self.textarea = textarea_with(msg);
+                        self.history_index = Some(index);
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                } if self.mode != Mode::Normal => match self.history_index {
+                    Some(0) => {
+                        self.textarea = textarea_with("");
+                        self.history_index = None;
+                    }
+                    Some(index) => {
+                        self.textarea = textarea_with(&self.message_history[index - 1]);
+                        self.history_index = Some(index - 1);
+                    }
+                    None => {}
+                },
+                _ => {
+                    if self.mode != Mode::Normal {
+                        // TODO: abstract over crossterm Key
+                        self.textarea.input(key);
+                    }
+                }
+            }
+        }
+
+        if key.code != KeyCode::Tab {
+            self.channel_suggestions.clear();
+            self.username_completion = None;
+        }
+        // Deleting the `:` leaves command mode, like in Vim
+        if self.mode == Mode::Command && self.textarea.is_empty() {
+            self.mode = Mode::Normal;
+        }
+        self.update_autocomplete();
+        self.request_redraw = true;
+    }
+
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.quit = true,
+            Action::InsertMode => self.mode = Mode::Insert,
+            Action::CommandMode => {
+                self.textarea = textarea_with(":");
+                self.mode = Mode::Command;
+            }
+            Action::NextTab => self.cycle_tab(1),
+            Action::PrevTab => self.cycle_tab(-1),
+            Action::ScrollOlder => self.scroll_active_chat(1),
+            Action::ScrollNewer => self.scroll_active_chat(-1),
+            Action::PageUp => self.scroll_active_chat(10),
+            Action::PageDown => self.scroll_active_chat(-10),
+            Action::ScrollToNewest => self.scroll_active_chat(isize::MIN),
+            Action::Reply => self.reply_to_selected(),
+            Action::DebugView => self.debug_view = !self.debug_view,
+            Action::Filter => self.buffer_search = Some(String::new()),
+            Action::Search => self.search = Some(Search::default()),
+            Action::Rewards => {
+                self.toggle_side_panel(SidePanel::Rewards);
+                if let Some(channel) = self
+                    .active_tab
+                    .clone()
+                    .filter(|_| self.side_panel == Some(SidePanel::Rewards))
+                {
+                    self.rewards.clear();
+                    let _ = self.handler_tx.send(ev::Send::FetchRewards(channel));
+                }
+            }
+            Action::Schedule => self.toggle_side_panel(SidePanel::Schedule),
+            Action::Leaderboard => self.toggle_side_panel(SidePanel::Leaderboard),
+            Action::Streams => self.toggle_side_panel(SidePanel::Streams),
+            Action::EmoteOnly => self.emote_only_view = !self.emote_only_view,
+            Action::Tasks => {
+                self.task_panel = !self.task_panel;
+                if self.task_panel {
+                    let _ = self.handler_tx.send(ev::Send::FetchCacheStats);
+                }
+            }
+            Action::ChannelList => self.channel_list = !self.channel_list,
+            Action::SplitView => self.toggle_split(),
+            Action::SwitchPane => self.switch_pane(),
+            Action::UserList => {
+                self.user_list = !self.user_list;
+                self.user_list_scroll = 0;
+            }
+            Action::Predictions => self.toggle_side_panel(SidePanel::Predictions),
+            Action::Drops if !self.cfg.drops => {
+                self.modal = Some(Modal::Alert(
+                    "Drops are off, set `drops = true` in the config to try them".into(),
+                ));
+            }
+            Action::Drops => {
+                self.toggle_side_panel(SidePanel::Drops);
+                if self.side_panel == Some(SidePanel::Drops) {
+                    let _ = self.handler_tx.send(ev::Send::FetchDrops);
+                }
+            }
+            Action::NextAccount => self.cycle_account(),
+            Action::EmotePicker => self.open_emote_picker(),
+        }
+    }
+
+    /// Chat history is kept for channels that are still in the config
+    fn config_reload_event(&mut self, cfg: Result<ReloadedConfig, String>) {
+        let cfg = match cfg {
+            Ok(ReloadedConfig(cfg)) => cfg,
+            Err(e) => {
+                self.modal = Some(Modal::Alert(format!("Config not reloaded: {e}")));
+                self.request_redraw = true;
+                return;
+            }
+        };
+        let old = std::mem::replace(&mut self.cfg, cfg);
+        self.mention_finders = self.cfg.mention_finders();
+        let mut errors = Vec::new();
+        match compile_highlights(&self.cfg) {
+            Ok(highlights) => self.highlights = highlights,
+            Err(e) => errors.push(format!("Highlights not reloaded: {e}")),
+        }
+        match keybindings::keymap(&self.cfg.keybindings) {
+            Ok(keymap) => self.keymap = keymap,
+            Err(e) => errors.push(format!("Keybindings not reloaded: {e}")),
+        }
+        match Theme::from_config(&self.cfg.theme) {
+            Ok(theme) => self.theme = theme,
+            Err(e) => errors.push(format!("Theme not reloaded: {e}")),
+        }
+
+        for chat in self.tabs.values_mut() {
+            chat.apply_config(&self.cfg);
+        }
+        for channel in &old.channels {
+            if !self.cfg.channels.contains(channel) {
+                self.close_tab(channel);
+            }
+        }
+        for channel in &self.cfg.channels {
+            if !self.tabs.contains_key(channel.as_ref()) {
+                self.tabs.insert(channel.to_string(), Chat::new(&self.cfg));
+                let _ = self.handler_tx.send(ev::Send::Join(channel.to_string()));
+            }
+        }
+        if !self
+            .active_tab
+            .as_ref()
+            .is_some_and(|tab| self.tabs.contains_key(tab))
+        {
+            self.switch_tab(0);
+        }
+        if let Some(channel) = self.active_tab.clone() {
+            for text in errors {
+                self.message_event(Message::system(channel.clone(), text));
+            }
+        }
+        self.request_redraw = true;
+    }
+
+    /// Moves from the main account through the extra ones and back
+    fn cycle_account(&mut self) {
+        let accounts = &self.cfg.accounts;
+        let next = match &self.active_account {
+            None => accounts.first(),
+            Some(active) => accounts
+                .iter()
+                .position(|account| &account.username == active)
+                .and_then(|i| accounts.get(i + 1)),
+        };
+        self.active_account = next.map(|account| account.username.clone());
+    }
+
+    /// Returns whether the key was used by the emote popup
+    fn autocomplete_key_event(&mut self, key: KeyEvent) -> bool {
+        let Some(autocomplete) = &mut self.autocomplete else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Up => {
+                autocomplete.selected = autocomplete.selected.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                autocomplete.selected =
+                    (autocomplete.selected + 1).min(autocomplete.candidates.len() - 1);
+            }
+            KeyCode::Tab | KeyCode::Enter => {
+                let name = autocomplete.candidates[autocomplete.selected].clone();
+                if let Some(word) = self.autocomplete_word() {
+                    for _ in 0..word.chars().count() {
+                        self.textarea.delete_char();
+                    }
+                }
+                self.textarea.insert_str(format!("{name} "));
+                self.autocomplete = None;
+            }
+            KeyCode::Esc => self.autocomplete = None,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The `:name` word right before the cursor
+    fn autocomplete_word(&self) -> Option<String> {
+        let (row, col) = self.textarea.cursor();
+        let before = self.textarea.lines()[row]
+            .chars()
+            .take(col)
+            .collect::<String>();
+        let word = before.rsplit(char::is_whitespace).next()?;
+        let query = word.strip_prefix(':')?;
+        (query.chars().count() >= AUTOCOMPLETE_MIN_LEN).then(|| word.to_string())
+    }
+
+    fn update_autocomplete(&mut self) {
+        let query = self
+            .autocomplete_word()
+            .filter(|_| self.mode == Mode::Insert)
+            .map(|word| word[1..].to_lowercase());
+        let Some(query) = query else {
+            self.autocomplete = None;
+            return;
+        };
+
+        let mut candidates = self
+            .emotes
+            .names()
+            .filter(|name| name.to_lowercase().starts_with(&query))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        candidates.truncate(AUTOCOMPLETE_LIMIT);
+
+        if candidates.is_empty() {
+            self.autocomplete = None;
+            return;
+        }
+        // Keep the selection on the same emote while typing narrows the list
+        let selected = self
+            .autocomplete
+            .as_ref()
+            .and_then(|old| old.candidates.get(old.selected))
+            .and_then(|name| candidates.iter().position(|c| c == name))
+            .unwrap_or(0);
+        self.autocomplete = Some(Autocomplete {
+            candidates,
+            selected,
+        });
+    }
+
+    fn active_chat_mut(&mut self) -> Option<&mut Chat<'a>> {
+        self.tabs.get_mut(self.active_tab.as_ref()?)
+    }
+
+    /// Wheel scrolls the active chat, middle-clicking a tab name closes it
+    fn mouse_event(&mut self, mouse: MouseEvent) {
+        let over_user_list = self.user_list_area.is_some_and(|area| {
+            area.contains(ratatui::layout::Position::new(mouse.column, mouse.row))
+        });
+        match mouse.kind {
+            MouseEventKind::ScrollUp if over_user_list => {
+                self.user_list_scroll = self
+                    .user_list_scroll
+                    .saturating_sub(MOUSE_SCROLL_LINES as usize);
+                self.request_redraw = true;
+            }
+            MouseEventKind::ScrollDown if over_user_list => {
+                self.user_list_scroll += MOUSE_SCROLL_LINES as usize;
+                self.request_redraw = true;
+            }
+            MouseEventKind::ScrollUp => self.scroll_active_chat(MOUSE_SCROLL_LINES),
+            MouseEventKind::ScrollDown => self.scroll_active_chat(-MOUSE_SCROLL_LINES),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some((area, offset)) = self.channel_list_area else {
+                    return;
+                };
+                let inner = Block::bordered().inner(area);
+                if inner.contains(ratatui::layout::Position::new(mouse.column, mouse.row)) {
+                    self.switch_tab(offset + usize::from(mouse.row - inner.y));
+                    self.request_redraw = true;
+                }
+            }
+            MouseEventKind::Down(MouseButton::Right) if over_user_list => {
+                self.user_list_context_menu(mouse.column, mouse.row);
+            }
+            MouseEventKind::Down(MouseButton::Middle) => {
+                if let Some((channel, _)) = self.tab_title_areas.iter().find(|(_, area)| {
+                    area.contains(ratatui::layout::Position::new(mouse.column, mouse.row))
+                }) {
+                    let channel = channel.clone();
+                    self.modal = Some(Modal::Confirm {
+                        message: format!("Close {channel}?"),
+                        action: Box::new(move |state| state.close_tab(&channel)),
+                    });
+                    self.request_redraw = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Opens the context menu for the chatter shown at `column`, `row` in the user list
+    fn user_list_context_menu(&mut self, column: u16, row: u16) {
+        let (Some(area), Some(channel)) = (self.user_list_area, self.active_tab.clone()) else {
+            return;
+        };
+        let inner = Block::bordered().inner(area);
+        if !inner.contains(ratatui::layout::Position::new(column, row)) {
+            return;
+        }
+        let index = self.user_list_scroll + usize::from(row - inner.y);
+        let Some(username) = self
+            .tabs
+            .get(&channel)
+            .and_then(|chat| chat.chatters(self.cfg.user_list_ttl()).nth(index))
+            .map(|(username, _)| username.clone())
+        else {
+            return;
+        };
+        self.context_menu = Some(ContextMenu { channel, username });
+        self.request_redraw = true;
+    }
+
+    /// The other pane starts on the tab after the active one
+    fn toggle_split(&mut self) {
+        if self.split_mode {
+            self.split_mode = false;
+            self.focus_right = false;
+            return;
+        }
+        if self.tabs.len() < 2 {
+            return;
+        }
+        if !self
+            .secondary_active_tab
+            .as_ref()
+            .is_some_and(|tab| self.tabs.contains_key(tab))
+        {
+            let index = self
+                .active_tab
+                .as_ref()
+                .and_then(|tab| self.tabs.get_index_of(tab))
+                .map_or(0, |index| (index + 1) % self.tabs.len());
+            self.secondary_active_tab = self.tabs.get_index(index).map(|(tab, _)| tab.clone());
+        }
+        self.split_mode = true;
+    }
+
+    /// Moves focus to the other pane, which makes its tab the active one
+    fn switch_pane(&mut self) {
+        if !self.split_mode || self.secondary_active_tab.is_none() {
+            return;
+        }
+        self.save_draft();
+        std::mem::swap(&mut self.active_tab, &mut self.secondary_active_tab);
+        self.restore_draft();
+        self.focus_right = !self.focus_right;
+    }
+
+    /// Removes the tab and its draft, leaving the channel
+    fn close_tab(&mut self, channel: &str) {
+        let Some(index) = self.tabs.get_index_of(channel) else {
+            return;
+        };
+        self.tabs.shift_remove_index(index);
+        self.drafts.shift_remove(channel);
+        if self.secondary_active_tab.as_deref() == Some(channel) {
+            self.secondary_active_tab = None;
+        }
+        if channel != WHISPERS_TAB {
+            let _ = self.handler_tx.send(ev::Send::Part(channel.to_string()));
+        }
+        if self.active_tab.as_deref() == Some(channel) {
+            self.active_tab = None;
+            self.switch_tab(index.saturating_sub(1));
+        }
+        self.request_redraw = true;
+    }
+
+    fn scroll_active_chat(&mut self, delta: isize) {
+        let smooth = self.cfg.smooth_scroll;
+        if let Some(chat) = self.active_chat_mut() {
+            chat.scroll_by(delta, smooth);
+        }
+
+        if smooth {
+            let event_tx = self.event_tx.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(SCROLL_FRAME);
+                for _ in 0..=SCROLL_FRAMES {
+                    interval.tick().await;
+                    let _ = event_tx.send(ev::In::Redraw);
+                }
+            });
+        }
+    }
+
+    /// The modal is put back unless the key closes it
+    fn modal_key_event(&mut self, modal: Modal, key: KeyEvent) {
+        match modal {
+            Modal::Alert(_) => {}
+            Modal::Confirm { message, action } => match key.code {
+                KeyCode::Char('y' | 'Y') | KeyCode::Enter => action(self),
+                KeyCode::Char('n' | 'N') | KeyCode::Esc => {}
+                _ => self.modal = Some(Modal::Confirm { message, action }),
+            },
+            Modal::Picker {
+                title,
+                items,
+                mut query,
+                mut selected,
+                action,
+            } => {
+                match key.code {
+                    KeyCode::Esc => return,
+                    KeyCode::Enter => {
+                        if let Some(item) = picker_matches(&items, &query).nth(selected) {
+                            let item = item.clone();
+                            action(self, item);
+                        }
+                        return;
+                    }
+                    KeyCode::Up => selected = selected.saturating_sub(1),
+                    KeyCode::Down => {
+                        let count = picker_matches(&items, &query).count();
+                        selected = (selected + 1).min(count.saturating_sub(1));
+                    }
+                    KeyCode::Backspace => {
+                        query.pop();
+                        selected = 0;
+                    }
+                    KeyCode::Char(c) => {
+                        query.push(c);
+                        selected = 0;
+                    }
+                    _ => {}
+                }
+                self.modal = Some(Modal::Picker {
+                    title,
+                    items,
+                    query,
+                    selected,
+                    action,
+                });
+            }
+        }
+    }
+
+    /// Every loaded emote, the picked one is typed into the message input
+    fn open_emote_picker(&mut self) {
+        let mut items = self.emotes.names().map(String::from).collect::<Vec<_>>();
+        items.sort_unstable_by_key(|name| name.to_lowercase());
+        self.modal = Some(Modal::Picker {
+            title: "Emotes".into(),
+            items,
+            query: String::new(),
+            selected: 0,
+            action: Box::new(|state, name| {
+                state.textarea.insert_str(format!("{name} "));
+                if state.mode == Mode::Normal {
+                    state.mode = Mode::Insert;
+                }
+            }),
+        });
+    }
+
+    fn context_menu_key_event(&mut self, menu: ContextMenu, key: KeyEvent) {
+        let ContextMenu { channel, username } = menu;
+        let KeyCode::Char(c) = key.code else {
+            return;
+        };
+
+        match c.to_ascii_lowercase() {
+            'c' => {
+                self.copy_to_clipboard(&username);
+                self.message_event(Message::system(channel, format!("Copied {username}")));
+            }
+            // Twitch only allows following from its own site
+            'f' | 'v' => {
+                let _ = open::that_detached(format!("https://www.twitch.tv/{username}"));
+            }
+            'b' => {
+                let _ = self.handler_tx.send(ev::Send::Ban {
+                    channel,
+                    username,
+                    duration: None,
+                });
+            }
+            't' => {
+                let _ = self.handler_tx.send(ev::Send::Ban {
+                    channel,
+                    username,
+                    duration: Some(TIMEOUT_DURATION),
+                });
+            }
+            'i' => {
+                let login = username.to_lowercase();
+                let ignored = self.ignored_users.contains(&login)
+                    || self.session_ignored_users.contains(&login);
+                self.ignore_command(&username, !ignored);
+            }
+            'e' => self.expand_selected(),
+            _ => {}
+        }
+    }
+
+    /// Sets the terminal's clipboard with an OSC 52 sequence
+    fn copy_to_clipboard(&self, text: &str) {
+        use base64::Engine;
+        use std::io::Write;
+
+        let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "\x1b]52;c;{encoded}\x07");
+        let _ = stdout.flush();
+    }
+
+    /// Starts a reply to the selected message, mentioning its sender
+    fn reply_to_selected(&mut self) {
+        let Some(message) = self
+            .selected_message()
+            .filter(|message| message.kind == MessageKind::Chat)
+        else {
+            return;
+        };
+        let Some(id) = message.id.clone() else {
+            return;
+        };
+
+        self.textarea = textarea_with(&format!("@{} ", message.username));
+        self.reply_to = Some(id);
+        self.mode = Mode::Insert;
+    }
+
+    /// Background tasks with their state and how long ago they last did something
+    fn task_table(&self) -> Table<'a> {
+        let now = Local::now();
+        let rows = self.tasks.iter().map(|(name, (state, at))| {
+            let state = match state {
+                TaskState::Running => Span::raw("running").green(),
+                TaskState::Retrying => Span::raw("retrying").yellow(),
+                TaskState::Finished => Span::raw("finished").dim(),
+                TaskState::Failed(e) => Span::raw(format!("failed: {e}")).red(),
+            };
+            let seconds = (now - *at).num_seconds().max(0);
+            Row::new(vec![
+                Line::from(name.clone()),
+                Line::from(state),
+                Line::from(format!("{seconds}s ago")).dim(),
+            ])
+        });
+
+        Table::new(
+            rows,
+            [
+                Constraint::Length(24),
+                Constraint::Fill(1),
+                Constraint::Length(10),
+            ],
+        )
+        .header(Row::new(vec!["Task", "State", "Active"]).bold())
+        .block(
+            Block::bordered()
+                .title("Background tasks")
+                .title_alignment(Alignment::Center),
+        )
+    }
+
+    fn task_event(&mut self, name: String, state: TaskState, at: DateTime<Local>) {
+        self.tasks.insert(name, (state, at));
+        self.request_redraw |= self.task_panel;
+    }
+
+    /// Raw IRC line of the selected message followed by a hex dump of its first 64 bytes of text
+    fn debug_lines(&self) -> Vec<Line<'static>> {
+        let Some(message) = self.selected_message() else {
+            return vec![Line::from("Select a message to inspect it").dim()];
+        };
+
+        let mut lines = vec![Line::from(
+            message
+                .raw
+                .clone()
+                .unwrap_or_else(|| "(not received over IRC)".into()),
+        )];
+        lines.extend(
+            message.msg.as_bytes()[..message.msg.len().min(64)]
+                .chunks(16)
+                .map(|chunk| {
+                    let hex: Vec<_> = chunk.iter().map(|b| format!("{b:02X}")).collect();
+                    Line::from(hex.join(" ")).yellow()
+                }),
+        );
+        lines
+    }
+
+    fn selected_message(&self) -> Option<&Message> {
+        let chat = self.tabs.get(self.active_tab.as_ref()?)?;
+        chat.lines.get(chat.selected?)?.message.as_ref()
+    }
+
+    fn expand_selected(&mut self) {
+        if let Some(chat) = self
+            .active_tab
+            .as_ref()
+            .and_then(|tab| self.tabs.get_mut(tab))
+        {
+            chat.expand_selected(&self.theme, &self.emotes);
+        }
+    }
+
+    fn search_key_event(&mut self, key: KeyEvent) {
+        let results = self
+            .search
+            .as_ref()
+            .map_or(0, |search| self.search_results(&search.query).len());
+        let Some(search) = &mut self.search else {
+            return;
+        };
+
+        match key.code {
+            KeyCode::Esc => self.search = None,
+            KeyCode::Up => search.selected = search.selected.saturating_sub(1),
+            KeyCode::Down => search.selected = (search.selected + 1).min(results.saturating_sub(1)),
+            KeyCode::Backspace => {
+                search.query.pop();
+                search.selected = 0;
+            }
+            KeyCode::Char(c) => {
+                search.query.push(c);
+                search.selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Edits the filter text, the chat can still be scrolled meanwhile
+    fn buffer_search_key_event(&mut self, key: KeyEvent) {
+        let Some(query) = &mut self.buffer_search else {
+            return;
+        };
+
+        match key.code {
+            KeyCode::Esc => self.buffer_search = None,
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) => query.push(c),
+            KeyCode::PageUp => self.scroll_active_chat(10),
+            KeyCode::PageDown => self.scroll_active_chat(-10),
+            _ => {}
+        }
+    }
+
+    /// Messages matching the search query in every tab, newest first
+    fn search_results(&self, query: &str) -> Vec<(&String, &Message)> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let query = query.to_lowercase();
+        let mut results: Vec<_> = self
+            .tabs
+            .iter()
+            .flat_map(|(channel, chat)| chat.lines.iter().map(move |line| (channel, line)))
+            .filter_map(|(channel, line)| Some((channel, line.received, line.message.as_ref()?)))
+            .filter(|(_, _, message)| message.msg.to_lowercase().contains(&query))
+            .collect();
+
+        results.sort_by_key(|(_, received, _)| Reverse(*received));
+        results
+            .into_iter()
+            .map(|(channel, _, message)| (channel, message))
+            .collect()
+    }
+
+    fn toggle_side_panel(&mut self, panel: SidePanel) {
+        self.side_panel = if self.side_panel == Some(panel) {
+            None
+        } else {
+            Some(panel)
+        };
+    }
+
+    /// Top 10 gifters, most subs first
+    fn leaderboard(&self) -> Table<'a> {
+        let mut gifters: Vec<_> = self.gift_totals.iter().collect();
+        gifters.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        let rows = gifters
+            .into_iter()
+            .take(10)
+            .enumerate()
+            .map(|(i, (name, count))| {
+                Row::new(vec![format!("{}.", i + 1), name.clone(), count.to_string()])
+            });
+
+        Table::new(
+            rows,
+            [
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Length(6),
+            ],
+        )
+        .header(Row::new(vec!["", "Gifter", "Subs"]).bold())
+        .block(
+            Block::bordered()
+                .title("Gift leaderboard")
+                .title_alignment(Alignment::Center),
+        )
+    }
+
+    /// Top 10 emotes used in the active channel
+    fn emote_leaderboard(&self) -> Table<'a> {
+        let mut emotes: Vec<_> = self
+            .active_tab
+            .as_ref()
+            .and_then(|tab| self.emote_counts.get(tab))
+            .into_iter()
+            .flatten()
+            .collect();
+        emotes.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        let rows = emotes
+            .into_iter()
+            .take(10)
+            .enumerate()
+            .map(|(i, (name, count))| {
+                Row::new(vec![format!("{}.", i + 1), name.clone(), count.to_string()])
+            });
+
+        Table::new(
+            rows,
+            [
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Length(8),
+            ],
+        )
+        .header(Row::new(vec!["", "Emote", "Uses"]).bold())
+        .block(
+            Block::bordered()
+                .title("Emote leaderboard")
+                .title_alignment(Alignment::Center),
+        )
+    }
+
+    fn count_emotes(&mut self, message: &Message) {
+        let counts = self
+            .emote_counts
+            .entry(message.channel.clone())
+            .or_default();
+        for token in emote_tokenizer::tokenize(&message.msg, |word| self.emotes.is_known(word)) {
+            if let emote_tokenizer::Token::Emote(name) = token {
+                *counts.entry(name.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    /// Recent predictions with their winner and how our bet went. Twitch only reports the top
+    /// predictors of each outcome, any other bet, or no bet at all, shows as `?`
+    fn prediction_history(&self) -> Table<'a> {
+        let rows = self.predictions.iter().map(|prediction| {
+            let bet = prediction
+                .predictors
+                .iter()
+                .find(|(login, _, _)| login.eq_ignore_ascii_case(&self.cfg.username));
+            Row::new(vec![
+                format!("{} {}", prediction.channel, prediction.title),
+                prediction
+                    .winner
+                    .clone()
+                    .unwrap_or_else(|| "Canceled".into()),
+                match bet {
+                    Some((_, used, won)) if *won > 0 => format!("+{}", won.saturating_sub(*used)),
+                    Some((_, used, _)) => format!("-{used}"),
+                    None => "?".into(),
+                },
+            ])
+        });
+
+        Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(16),
+                Constraint::Length(8),
+            ],
+        )
+        .header(Row::new(vec!["Prediction", "Winner", "Bet"]).bold())
+        .block(
+            Block::bordered()
+                .title("Predictions")
+                .title_bottom(Line::from(" ? not among the top predictors ").dim())
+                .title_alignment(Alignment::Center),
+        )
+    }
+
+    fn prediction_end_event(&mut self, prediction: CompletedPrediction) {
+        if self.predictions.len() == PREDICTION_HISTORY_LEN {
+            self.predictions.pop_back();
+        }
+        self.predictions.push_front(prediction);
+        if self.side_panel == Some(SidePanel::Predictions) {
+            self.request_redraw = true;
+        }
+    }
+
+    fn user_cleared_event(&mut self, channel: String, user: String, timeout: Option<Duration>) {
+        let Some(chat) = self.tabs.get_mut(&channel) else {
+            return;
+        };
+        chat.delete_messages_from(&user);
+        let text = match timeout {
+            Some(timeout) => format!("{user} was timed out for {}s", timeout.as_secs()),
+            None => format!("{user} was banned"),
+        };
+        self.message_event(Message::system(channel, text));
+    }
+
+    fn message_deleted_event(&mut self, channel: String, id: String) {
+        let Some(chat) = self.tabs.get_mut(&channel) else {
+            return;
+        };
+        if chat.delete_message(&id) && self.active_tab.as_ref() == Some(&channel) {
+            self.request_redraw = true;
+        }
+    }
+
+    /// The chat line comes from the matching system event
+    fn raid_event(&mut self, raider: String, viewers: u64) {
+        self.raid_popup = Some((
+            Instant::now() + RAID_POPUP_DURATION,
+            format!("⚔ {raider} is raiding with {viewers} viewers!"),
+        ));
+        self.request_redraw = true;
+    }
+
+    /// Watched categories and their live streams, headers are not selectable
+    fn render_streams(frame: &mut Frame, area: Rect, state: &State) {
+        let mut items = Vec::new();
+        let mut selected = None;
+        let mut index = 0;
+        for (category, streams) in &state.category_streams {
+            items.push(Line::from(category.clone()).bold());
+            for stream in streams {
+                if index == state.selected_stream {
+                    selected = Some(items.len());
+                }
+                index += 1;
+                items.push(Line::from(vec![
+                    Span::styled(
+                        stream.login.clone(),
+                        Style::default().fg(state.theme.username.unwrap_or(Color::Blue)),
+                    ),
+                    Span::styled(format!(" {} ", stream.viewers), Style::default().red()),
+                    Span::styled(stream.title.clone(), Style::default().dim()),
+                ]));
+            }
+        }
+
+        let mut list_state = ListState::default().with_selected(selected);
+        frame.render_stateful_widget(
+            List::new(items)
+                .highlight_style(Style::default().reversed())
+                .block(
+                    Block::bordered()
+                        .title("Streams")
+                        .title_alignment(Alignment::Center),
+                ),
+            area,
+            &mut list_state,
+        );
+    }
+
+    /// Active drop campaigns with time left, the selected one can be expanded to show its drops
+    fn render_drops(frame: &mut Frame, area: Rect, state: &State) {
+        let now = Local::now();
+        let block = Block::bordered()
+            .title("Drops")
+            .title_alignment(Alignment::Center);
+        // Errors are wrapped, the panel is narrow
+        if let Some(error) = &state.drops_error {
+            frame.render_widget(
+                Paragraph::new(error.clone())
+                    .red()
+                    .wrap(Wrap { trim: false })
+                    .block(block),
+                area,
+            );
+            return;
+        }
+
+        let mut items = Vec::new();
+        let mut selected = None;
+        for (index, campaign) in state.drop_campaigns.iter().enumerate() {
+            if index == state.selected_drop {
+                selected = Some(items.len());
+            }
+
+            let minutes = (campaign.end - now).num_minutes().max(0);
+            items.push(Line::from(vec![
+                Span::styled(campaign.name.clone(), Style::default().blue()),
+                Span::styled(format!(" {} ", campaign.game), Style::default().dim()),
+                Span::styled(
+                    format!("{}d {}h left", minutes / (24 * 60), minutes / 60 % 24),
+                    Style::default().red(),
+                ),
+            ]));
+
+            if state.expanded_drop == Some(index) {
+                for (name, required_minutes) in &campaign.drops {
+                    items.push(Line::from(format!(
+                        "  {name}: watch {}h {:02}m",
+                        required_minutes / 60,
+                        required_minutes % 60
+                    )));
+                }
+            }
+        }
+
+        let mut list_state = ListState::default().with_selected(selected);
+        frame.render_stateful_widget(
+            List::new(items)
+                .highlight_style(Style::default().reversed())
+                .block(block),
+            area,
+            &mut list_state,
+        );
+    }
+
+    /// Custom rewards with their cost, disabled ones are dimmed
+    fn render_rewards(frame: &mut Frame, area: Rect, state: &State) {
+        let items = state.rewards.iter().map(|reward| {
+            let line = Line::from(vec![
+                Span::raw(if reward.enabled { "[x] " } else { "[ ] " }),
+                Span::styled(reward.title.clone(), Style::default().blue()),
+                Span::styled(format!(" {}", reward.cost), Style::default().dim()),
+            ]);
+            if reward.enabled {
+                line
+            } else {
+                line.dim()
+            }
+        });
+
+        let mut list_state = ListState::default()
+            .with_selected((!state.rewards.is_empty()).then_some(state.selected_reward));
+        frame.render_stateful_widget(
+            List::new(items)
+                .highlight_style(Style::default().reversed())
+                .block(
+                    Block::bordered()
+                        .title("Rewards [Space] toggle")
+                        .title_alignment(Alignment::Center),
+                ),
+            area,
+            &mut list_state,
+        );
+    }
+
+    fn rewards_event(&mut self, channel: String, rewards: Vec<CustomReward>) {
+        if self.active_tab.as_ref() != Some(&channel) {
+            return;
+        }
+        self.rewards = rewards;
+        self.selected_reward = self
+            .selected_reward
+            .min(self.rewards.len().saturating_sub(1));
+        if self.side_panel == Some(SidePanel::Rewards) {
+            self.request_redraw = true;
+        }
+    }
+
+    fn toggle_selected_reward(&mut self) {
+        let (Some(channel), Some(reward)) = (
+            self.active_tab.clone(),
+            self.rewards.get(self.selected_reward),
+        ) else {
+            return;
+        };
+        let _ = self.handler_tx.send(ev::Send::SetRewardEnabled {
+            channel,
+            reward_id: reward.id.clone(),
+            enabled: !reward.enabled,
+        });
+    }
+
+    fn drops_event(&mut self, campaigns: Result<Vec<DropCampaign>, String>) {
+        match campaigns {
+            Ok(campaigns) => {
+                self.drop_campaigns = campaigns;
+                self.drops_error = None;
+            }
+            Err(e) => {
+                self.drop_campaigns.clear();
+                self.drops_error = Some(e);
+            }
+        }
+        self.selected_drop = self
+            .selected_drop
+            .min(self.drop_campaigns.len().saturating_sub(1));
+        self.expanded_drop = None;
+        if self.side_panel == Some(SidePanel::Drops) {
+            self.request_redraw = true;
+        }
+    }
+
+    fn join_selected_stream(&mut self) {
+        if let Some(stream) = self
+            .category_streams
+            .values()
+            .flatten()
+            .nth(self.selected_stream)
+        {
+            let login = stream.login.clone();
+            self.join(&login);
+        }
+    }
+
+    fn category_streams_event(&mut self, category: String, streams: Vec<LiveStream>) {
+        self.category_streams.insert(category, streams);
+        if self.side_panel == Some(SidePanel::Streams) {
+            self.request_redraw = true;
+        }
+    }
+
+    fn gift_event(&mut self, gifter: String, count: usize) {
+        if count > GIFT_BOMB_MIN {
+            self.gift_bomb = Some(GiftBomb {
+                gifter: gifter.clone(),
+                received: 0,
+                total: count,
+                deadline: Instant::now() + GIFT_BOMB_WINDOW,
+            });
+            self.request_redraw = true;
+        }
+
+        *self.gift_totals.entry(gifter).or_default() += count;
+        if self.side_panel == Some(SidePanel::Leaderboard) {
+            self.request_redraw = true;
+        }
+    }
+
+    /// Team members already open as a tab show up there instead
+    fn team_member_live_event(&mut self, login: String) {
+        let login = login.to_lowercase();
+        if self.tabs.contains_key(&format!("#{login}")) {
+            return;
+        }
+        if let Some(channel) = self.active_tab.clone() {
+            self.message_event(Message::system(channel, format!("🎮 {login} is now live!")));
+        }
+    }
+
+    fn whisper_event(&mut self, sender: String, text: String) {
+        self.tabs
+            .entry(WHISPERS_TAB.to_string())
+            .or_insert_with(|| Chat::new(&self.cfg));
+        self.whisper_alert = Some((Instant::now(), format!("✉ {sender}: {text}")));
+        self.message_event(Message::new(
+            WHISPERS_TAB.to_string(),
+            sender,
+            text,
+            MessageKind::Chat,
+        ));
+
+        // Redraw for every flash of the tab title, and once more to clear it
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(WHISPER_FLASH_INTERVAL);
+            for _ in 0..=WHISPER_ALERT_DURATION.as_millis() / WHISPER_FLASH_INTERVAL.as_millis() {
+                interval.tick().await;
+                if event_tx.send(ev::In::Redraw).is_err() {
+                    break;
+                }
+            }
+        });
+        self.request_redraw = true;
+    }
+
+    /// Whether the whisper tab title is highlighted this frame
+    fn whisper_flash(&self) -> bool {
+        self.whisper_alert.as_ref().is_some_and(|(received, _)| {
+            let elapsed = received.elapsed();
+            elapsed < WHISPER_ALERT_DURATION
+                && (elapsed.as_millis() / WHISPER_FLASH_INTERVAL.as_millis()).is_multiple_of(2)
+        })
+    }
+
+    fn community_gift_sub_event(&mut self, gifter: String) {
+        let Some(bomb) = self.gift_bomb.as_mut().filter(|b| b.gifter == gifter) else {
+            return;
+        };
+
+        bomb.received += 1;
+        if bomb.received >= bomb.total {
+            self.gift_bomb = None;
+        }
+        self.request_redraw = true;
+    }
+
+    /// Sends the textarea contents, or runs it as a command
+    fn submit(&mut self) {
+        let text = self.textarea.lines().join(" ");
+
+        if let Some(channel) = text.strip_prefix("/join ") {
+            self.join(channel.trim());
+        } else if let Some(path) = text.strip_prefix(":export ") {
+            self.export_log(path.trim());
+        } else if let Some((username, ignore)) = text
+            .strip_prefix("/ignore ")
+            .map(|username| (username, true))
+            .or_else(|| {
+                text.strip_prefix("/unignore ")
+                    .map(|username| (username, false))
+            })
+        {
+            self.ignore_command(username.trim().trim_start_matches('@'), ignore);
+        } else if let Some(channel) = text
+            .strip_prefix("/refresh-emotes")
+            .filter(|args| args.is_empty() || args.starts_with(' '))
+        {
+            let channel = match channel.trim() {
+                "" => self.active_tab.clone().filter(|tab| tab != WHISPERS_TAB),
+                channel => Some(format!(
+                    "#{}",
+                    channel.trim_start_matches('#').to_lowercase()
+                )),
+            };
+            let Some(channel) = channel else {
+                return;
+            };
+            let _ = self.handler_tx.send(ev::Send::InvalidateCache(channel));
+        } else if text.trim() == ":save-ignore" {
+            let count = self.session_ignored_users.len();
+            self.ignored_users
+                .extend(self.session_ignored_users.drain());
+            if let Some(channel) = self.active_tab.clone() {
+                self.message_event(Message::system(
+                    channel,
+                    format!("{count} ignored users will be kept across sessions"),
+                ));
+            }
+        } else if let Some(args) = text
+            .strip_prefix("/commercial")
+            .filter(|args| args.is_empty() || args.starts_with(' '))
+        {
+            let Some(channel) = self.active_tab.clone().filter(|tab| tab != WHISPERS_TAB) else {
+                return;
+            };
+            let length = match args.trim() {
+                "" => Some(COMMERCIAL_LENGTHS[0]),
+                length => length
+                    .parse()
+                    .ok()
+                    .filter(|length| COMMERCIAL_LENGTHS.contains(length)),
+            };
+            let Some(length) = length else {
+                self.message_event(Message::system(
+                    channel,
+                    "Usage: /commercial [30|60|90|120|150|180]".into(),
+                ));
+                return;
+            };
+            let _ = self
+                .handler_tx
+                .send(ev::Send::Commercial { channel, length });
+        } else if let Some(args) = text.strip_prefix("/poll ") {
+            let Some(channel) = self.active_tab.clone().filter(|tab| tab != WHISPERS_TAB) else {
+                return;
+            };
+            match parse_poll(args) {
+                Some((title, choices, duration)) => {
+                    let _ = self.handler_tx.send(ev::Send::CreatePoll {
+                        channel,
+                        title,
+                        choices,
+                        duration,
+                    });
+                }
+                None => {
+                    self.message_event(Message::system(
+                        channel,
+                        r#"Usage: /poll "Question" "Option A" "Option B" [duration_seconds]"#
+                            .into(),
+                    ));
+                    return;
+                }
+            }
+        } else if let Some((username, vip)) = text
+            .strip_prefix("/vip ")
+            .map(|username| (username, true))
+            .or_else(|| {
+                text.strip_prefix("/unvip ")
+                    .map(|username| (username, false))
+            })
+        {
+            let Some(channel) = self.active_tab.clone().filter(|tab| tab != WHISPERS_TAB) else {
+                return;
+            };
+            let _ = self.handler_tx.send(ev::Send::Vip {
+                channel,
+                username: username.trim().trim_start_matches('@').to_lowercase(),
+                vip,
+            });
+        } else if let Some(channel) = self
+            .active_tab
+            .as_ref()
+            // Whispers can't be answered over chat
+            .filter(|tab| *tab != WHISPERS_TAB)
+        {
+            let message = Message {
+                account: self.active_account.clone(),
+                ..Message::new(
+                    channel.clone(),
+                    self.active_account
+                        .clone()
+                        .unwrap_or_else(|| self.cfg.username.to_string()),
+                    text,
+                    MessageKind::Chat,
+                )
+            };
+            let _ = self.handler_tx.send(match self.reply_to.take() {
+                Some(parent_id) => ev::Send::Reply {
+                    message: message.clone(),
+                    parent_id,
+                },
+                None => ev::Send::Message(message.clone()),
+            });
+            self.drafts.shift_remove(channel);
+
+            if self.message_history.len() == MESSAGE_HISTORY_LEN {
+                self.message_history.pop_back();
+            }
+            self.message_history.push_front(message.msg.clone());
+            self.history_index = None;
+
+            if let Some(tab) = self.tabs.get_mut(channel) {
+                tab.push_message(
+                    &self.mention_finders,
+                    &self.highlights,
+                    &self.theme,
+                    &self.emotes,
+                    message,
+                );
+            }
+        } else {
+            return;
+        }
+
+        self.textarea = textarea_with("");
+    }
+
+    /// `/ignore` only lasts for the session, `/unignore` also removes saved ignores
+    fn ignore_command(&mut self, username: &str, ignore: bool) {
+        let Some(channel) = self.active_tab.clone() else {
+            return;
+        };
+        if username.is_empty() {
+            self.message_event(Message::system(
+                channel,
+                "Usage: /ignore <username> or /unignore <username>".into(),
+            ));
+            return;
+        }
+
+        let login = username.to_lowercase();
+        let text = if ignore {
+            self.session_ignored_users.insert(login);
+            format!("Ignoring {username} for this session, :save-ignore to keep ignoring them")
+        } else {
+            let removed = self.session_ignored_users.remove(&login);
+            if self.ignored_users.remove(&login) || removed {
+                format!("No longer ignoring {username}")
+            } else if self.cfg.ignores(&channel, &login) {
+                format!("{username} is ignored in the config")
+            } else {
+                format!("{username} isn't ignored")
+            }
+        };
+        self.message_event(Message::system(channel, text));
+    }
+
+    /// Sends the active chat's messages, oldest first, to be written to `path`
+    fn export_log(&mut self, path: &str) {
+        let Some((channel, chat)) = self
+            .active_tab
+            .as_ref()
+            .and_then(|tab| self.tabs.get_key_value(tab))
+        else {
+            return;
+        };
+
+        let path = match path.strip_prefix("~/").zip(dirs::home_dir()) {
+            Some((path, home)) => home.join(path),
+            None => path.into(),
+        };
+        let text: String = chat
+            .lines
+            .iter()
+            .rev()
+            .filter_map(|line| line.message.as_ref())
+            .map(|message| {
+                format!(
+                    "[{}] {}: {}\n",
+                    message.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    message.username,
+                    message.msg
+                )
+            })
+            .collect();
+        let _ = self.handler_tx.send(ev::Send::ExportLog {
+            channel: channel.clone(),
+            path,
+            text,
+        });
+    }
+
+    /// Opens a tab for `channel` and makes it active
+    fn join(&mut self, channel: &str) {
+        let channel = format!("#{}", channel.trim_start_matches('#').to_lowercase());
+        if channel == "#" {
+            return;
+        }
+
+        if !self.tabs.contains_key(&channel) {
+            let _ = self.handler_tx.send(ev::Send::Join(channel.clone()));
+            self.tabs.insert(channel.clone(), Chat::new(&self.cfg));
+        }
+        self.set_active_tab(channel);
+    }
+
+    /// Cycles through channel suggestions for `/join #prefix`, searching on first press
+    fn complete_join(&mut self) {
+        let text = self.textarea.lines().join(" ");
+        let Some(query) = text.strip_prefix("/join #") else {
+            return;
+        };
+
+        if self.channel_suggestions.is_empty() {
+            if !query.is_empty() {
+                let _ = self
+                    .handler_tx
+                    .send(ev::Send::SearchChannels(query.to_string()));
+            }
+            return;
+        }
+
+        let index = self
+            .suggestion_index
+            .map_or(0, |i| (i + 1) % self.channel_suggestions.len());
+        self.suggestion_index = Some(index);
+        self.textarea = textarea_with(&format!("/join #{}", self.channel_suggestions[index]));
+    }
+
+    /// Completes the `@` word before the cursor from the active chat's user list, and moves
+    /// on to the next one on repeated presses. Returns whether there was anything to complete
+    fn complete_username(&mut self) -> bool {
+        if let Some(completion) = &mut self.username_completion {
+            let previous = completion.candidates[completion.index].chars().count();
+            completion.index = (completion.index + 1) % completion.candidates.len();
+            let next = completion.candidates[completion.index].clone();
+            for _ in 0..previous {
+                self.textarea.delete_char();
+            }
+            self.textarea.insert_str(next);
+            return true;
+        }
+
+        let (row, col) = self.textarea.cursor();
+        let before = self.textarea.lines()[row]
+            .chars()
+            .take(col)
+            .collect::<String>();
+        let Some(typed) = before
+            .rsplit(char::is_whitespace)
+            .next()
+            .and_then(|word| word.strip_prefix('@'))
+        else {
+            return false;
+        };
+        let prefix = typed.to_lowercase();
+
+        let candidates = self
+            .active_tab
+            .as_ref()
+            .and_then(|tab| self.tabs.get(tab))
+            .into_iter()
+            .flat_map(|chat| chat.chatters(COMPLETION_TTL))
+            .map(|(name, _)| name)
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        let Some(first) = candidates.first() else {
+            return false;
+        };
+
+        for _ in 0..typed.chars().count() {
+            self.textarea.delete_char();
+        }
+        self.textarea.insert_str(first);
+        self.username_completion = Some(UsernameCompletion {
+            candidates,
+            index: 0,
+        });
+        true
+    }
+
+    fn channel_suggestions_event(&mut self, query: String, channels: Vec<String>) {
+        // Ignore results for a query the user has since moved on from
+        if self.textarea.lines().join(" ") == format!("/join #{query}") {
+            self.channel_suggestions = channels;
+            self.suggestion_index = None;
+            self.request_redraw = true;
+        }
+    }
+
+    /// Moves `delta` tabs from the active one, wrapping around
+    fn cycle_tab(&mut self, delta: isize) {
+        let Some(index) = self
+            .active_tab
+            .as_ref()
+            .and_then(|tab| self.tabs.get_index_of(tab))
+        else {
+            self.switch_tab(0);
+            return;
+        };
+        let len = self.tabs.len() as isize;
+        self.switch_tab((index as isize + delta).rem_euclid(len) as usize);
+    }
+
+    /// Makes the tab at `index` active, keeping each channel's draft
+    fn switch_tab(&mut self, index: usize) {
+        let Some((channel, _)) = self.tabs.get_index(index) else {
+            return;
+        };
+        if self.active_tab.as_ref() == Some(channel) {
+            return;
+        }
+
+        let channel = channel.clone();
+        self.save_draft();
+        if let Some(chat) = self.tabs.get_mut(&channel) {
+            chat.unseen = 0;
+        }
+        self.set_active_tab(channel);
+        self.restore_draft();
+    }
+
+    /// Swaps the panes when `channel` is in the other one, so no channel is shown twice
+    fn set_active_tab(&mut self, channel: String) {
+        if self.secondary_active_tab.as_ref() == Some(&channel) {
+            self.secondary_active_tab = self.active_tab.take();
+        }
+        self.active_tab = Some(channel);
+    }
+
+    /// Stores the textarea contents as the active channel's draft
+    fn save_draft(&mut self) {
+        if let Some(channel) = &self.active_tab {
+            let text = self.textarea.lines().join("\n");
+            if text.is_empty() {
+                self.drafts.shift_remove(channel);
+            } else {
+                self.drafts.insert(channel.clone(), text);
+            }
+        }
+    }
+
+    /// Replaces the textarea contents with the active channel's draft
+    fn restore_draft(&mut self) {
+        let draft = self
+            .active_tab
+            .as_ref()
+            .and_then(|channel| self.drafts.get(channel));
+        self.textarea = textarea_with(draft.map_or("", |s| s.as_str()));
+        self.reply_to = None;
+    }
+
+    fn message_event(&mut self, message: Message) {
+        // The same message may be delivered by more than one source
+        if let Some(id) = &message.id {
+            if self.seen_msg_ids.put(id.clone(), ()).is_some() {
+                return;
+            }
+        }
+
+        if message.kind == MessageKind::Chat {
+            let login = message.username.to_lowercase();
+            if self.ignored_users.contains(&login)
+                || self.session_ignored_users.contains(&login)
+                || self.cfg.ignores(&message.channel, &login)
+            {
+                return;
+            }
+        }
+        // Only IRC chat carries badges, whispers and messages from other sources have none
+        if message.kind == MessageKind::Chat
+            && message.raw.is_some()
+            && message.channel != WHISPERS_TAB
+            && !message.username.eq_ignore_ascii_case(&self.cfg.username)
+            && self
+                .cfg
+                .show_only_type
+                .is_some_and(|user_type| !user_type.allows(&message.badges))
+        {
+            return;
+        }
+        if message.kind == MessageKind::Chat {
+            self.count_emotes(&message);
+        }
+
+        let shown = self.is_shown(&message.channel);
+        if let Some(c) = self.tabs.get_mut(&message.channel) {
+            if message.kind == MessageKind::Chat {
+                c.saw_chatter(&message.username, &message.badges, self.cfg.user_list_ttl());
+            }
+            if shown {
+                self.request_redraw = true;
+            } else if message.kind == MessageKind::Chat {
+                c.unseen += 1;
+                self.request_redraw = true;
+            }
+            c.push_message(
+                &self.mention_finders,
+                &self.highlights,
+                &self.theme,
+                &self.emotes,
+                message,
+            );
+        }
+    }
+
+    fn prune_messages_event(&mut self) {
+        if let Some(max_age) = self.cfg.max_message_age_seconds {
+            for chat in self.tabs.values_mut() {
+                chat.prune(Duration::from_secs(max_age));
+            }
+            self.request_redraw = true;
+        }
+    }
 
-pub struct Chat<'a> {
-    lines: VecDeque<Line<'a>>,
-    bg_darken: bool,
-}
+    fn schedule_event(&mut self, channel: String, segments: Vec<ScheduleSegment>) {
+        if let Some(chat) = self.tabs.get_mut(&channel) {
+            chat.schedule = segments;
+            if self.side_panel == Some(SidePanel::Schedule)
+                && self.active_tab.as_ref() == Some(&channel)
+            {
+                self.request_redraw = true;
+            }
+        }
+    }
 
-impl<'a> Chat<'a> {
-    pub fn new() -> Self {
-        Self {
-            lines: VecDeque::with_capacity(100),
-            bg_darken: false,
+    fn charity_event(&mut self, channel: String, campaign: Option<CharityCampaign>) {
+        if let Some(chat) = self.tabs.get_mut(&channel) {
+            chat.charity = campaign;
+            if self.active_tab.as_ref() == Some(&channel) {
+                self.request_redraw = true;
+            }
         }
     }
 
-    pub fn push_message(&mut self, mention_finder: &memchr::memmem::Finder, message: Message) {
-        let found_mention = mention_finder.find(message.msg.as_bytes()).is_some();
-        let line = Line::from(vec![
-            Span::styled(message.username, Style::default().blue()),
-            Span::styled(": ", Style::default()),
-            Span::styled(message.msg, Style::default()),
-        ])
-        .bg({
-            if found_mention {
-                Color::Red
-            } else if self.bg_darken {
-                Color::Black
-            } else {
-                Color::Reset
+    fn status_bar(&self) -> StatusBar<'_> {
+        let active_chat = self.active_tab.as_ref().and_then(|tab| self.tabs.get(tab));
+        StatusBar {
+            channel: self.active_tab.as_deref(),
+            connection: self.tasks.get("chat").map(|(state, _)| state),
+            mode: self.mode,
+            slow: active_chat
+                .and_then(|chat| chat.room_state.as_ref())
+                .and_then(|state| state.slow),
+            unread: self
+                .tabs
+                .iter()
+                .filter(|(_, chat)| chat.unseen > 0)
+                .map(|(channel, chat)| (channel.as_str(), chat.unseen))
+                .collect(),
+        }
+    }
+
+    /// Stream details and warnings shown below the chat, `None` hides the line
+    fn status_line(&self) -> Option<Line<'static>> {
+        let mut spans = Vec::new();
+        let active_chat = self.active_tab.as_ref().and_then(|tab| self.tabs.get(tab));
+
+        if let Some(account) = &self.active_account {
+            spans.push(Span::raw(format!("[as {account}] ")).cyan());
+        }
+
+        match active_chat.and_then(|chat| chat.stream_status.as_ref()) {
+            Some(Some(status)) => {
+                let minutes = (Local::now() - status.started_at).num_minutes().max(0);
+                spans.push(Span::raw(format!(
+                    "⏱ {}h {:02}m 👁 {} ",
+                    minutes / 60,
+                    minutes % 60,
+                    status.viewers
+                )));
             }
+            Some(None) => spans.push(Span::raw("[offline] ").dim()),
+            None => {}
+        }
+
+        // Twitch reports a delay of 0 unless the token belongs to the broadcaster
+        let own_channel = self.active_tab.as_ref().is_some_and(|tab| {
+            tab.trim_start_matches('#')
+                .eq_ignore_ascii_case(&self.cfg.username)
         });
+        match active_chat
+            .and_then(|chat| chat.delay)
+            .filter(|_| own_channel)
+        {
+            Some(0) => spans.push(Span::raw("[LL] ").green()),
+            Some(_) => spans.push(Span::raw("[delay] ").red()),
+            None => {}
+        }
 
-        self.bg_darken = !self.bg_darken;
+        if let Some(info) = active_chat.and_then(|chat| chat.stream_info.as_ref()) {
+            spans.push(Span::raw(format!("{} ", info.title)));
+            spans.push(Span::raw(format!("[{}] ", info.category)).dim());
+        }
 
-        if self.lines.len() == self.lines.capacity() {
-            self.lines.pop_back();
-            self.lines.push_front(line);
-        } else {
-            self.lines.push_front(line);
+        if self.eventsub_degraded > 0 {
+            spans.push(
+                Span::raw(format!(
+                    "{} stream event subscription(s) degraded, retrying",
+                    self.eventsub_degraded
+                ))
+                .yellow(),
+            );
         }
-    }
 
-    pub fn list(&self, title: String) -> List<'a> {
-        List::new(self.lines.clone())
-            .direction(ListDirection::BottomToTop)
-            .block(
-                Block::bordered()
-                    .title(title)
-                    .title_alignment(Alignment::Center),
-            )
+        (!spans.is_empty()).then(|| Line::from(spans))
     }
-}
 
-#[allow(unused)]
-struct State<'a> {
-    tabs: IndexMap<String, Chat<'a>>,
-    textarea_focused: bool,
-    active_tab: Option<String>,
-    textarea: TextArea<'a>,
-    quit: bool,
-    request_redraw: bool,
-    mention_finder: memchr::memmem::Finder<'a>,
-    handler_tx: UnboundedSender<ev::Send>,
-    cfg: Config,
-}
+    fn stream_info_event(&mut self, info: StreamInfo) {
+        if self.active_tab.as_ref() == Some(&info.channel) {
+            self.request_redraw = true;
+        }
+        if let Some(chat) = self.tabs.get_mut(&info.channel) {
+            chat.stream_info = Some(info);
+        }
+    }
 
-impl<'a> State<'a> {
-    fn new(
-        start_focused: bool,
-        mention_finder: memchr::memmem::Finder<'a>,
-        handler_tx: UnboundedSender<ev::Send>,
-        cfg: Config,
-    ) -> Self {
-        let mut textarea = TextArea::default();
-        textarea.set_block(Block::default().borders(Borders::ALL));
-        Self {
-            textarea_focused: start_focused,
-            active_tab: None,
-            tabs: IndexMap::default(),
-            textarea,
-            quit: false,
-            mention_finder,
-            request_redraw: false,
-            handler_tx,
-            cfg,
+    fn stream_delay_event(&mut self, channel: String, delay: u64) {
+        if let Some(chat) = self.tabs.get_mut(&channel) {
+            chat.delay = Some(delay);
+        }
+        if self.active_tab.as_ref() == Some(&channel) {
+            self.request_redraw = true;
         }
     }
 
-    fn key_event(&mut self, key: KeyEvent) {
-        match key {
-            KeyEvent {
-                code: KeyCode::Char('q'),
-                ..
-            } => {
-                if !self.textarea_focused {
-                    self.quit = true;
-                } else {
-                    self.textarea.input(key);
-                }
-            }
-            KeyEvent {
-                code: KeyCode::Char('i'),
-                ..
-            } => {
-                if !self.textarea_focused {
-                    self.textarea_focused = true;
-                } else {
-                    self.textarea.input(key);
-                }
+    fn animation_frame(&self) -> Option<usize> {
+        self.cfg.show_power_up_animations.then(|| {
+            (self.animation_start.elapsed().as_millis() / POWER_UP_FRAME.as_millis()) as usize
+        })
+    }
+
+    /// Tabs on screen, the active one and the other pane in split view
+    fn shown_tabs(&self) -> impl Iterator<Item = &String> {
+        let secondary = self
+            .secondary_active_tab
+            .as_ref()
+            .filter(|_| self.split_mode);
+        [self.active_tab.as_ref(), secondary].into_iter().flatten()
+    }
+
+    fn is_shown(&self, channel: &str) -> bool {
+        self.shown_tabs().any(|tab| tab == channel)
+    }
+
+    fn shown_chats(&self) -> impl Iterator<Item = &Chat<'a>> {
+        self.shown_tabs().filter_map(|tab| self.tabs.get(tab))
+    }
+
+    /// Whether animation ticks are needed, for power-ups or an animated emote on screen
+    fn is_animating(&self) -> bool {
+        self.cfg.show_power_up_animations
+            || self.shown_chats().any(|chat| {
+                chat.visible_emotes
+                    .iter()
+                    .any(|name| self.emotes.frame_delays(name).is_some())
+            })
+    }
+
+    /// Advances the animated emotes on screen, and only redraws while something animates
+    fn animation_tick_event(&mut self) {
+        let now = Instant::now();
+        let power_up_frame = self.animation_frame();
+        let power_ups = power_up_frame.is_some() && power_up_frame != self.power_up_frame;
+        self.power_up_frame = power_up_frame;
+        let secondary = self
+            .secondary_active_tab
+            .as_ref()
+            .filter(|_| self.split_mode);
+        for chat in [self.active_tab.as_ref(), secondary]
+            .into_iter()
+            .flatten()
+            .filter_map(|tab| self.tabs.get(tab))
+        {
+            if power_ups && chat.lines.iter().any(ChatLine::is_power_up) {
+                self.request_redraw = true;
             }
-            KeyEvent {
-                code: KeyCode::Esc, ..
-            } => {
-                if self.textarea_focused {
-                    self.textarea_focused = false;
-                } else {
-                    self.quit = true;
+            for name in &chat.visible_emotes {
+                let Some(delays) = self.emotes.frame_delays(name) else {
+                    continue;
+                };
+                let frame = self.emote_frames.entry(name.clone()).or_insert(EmoteFrame {
+                    index: 0,
+                    shown_at: now,
+                });
+                let delay = Duration::from_millis(delays[frame.index % delays.len()].into());
+                if now.duration_since(frame.shown_at) >= delay {
+                    frame.index = (frame.index + 1) % delays.len();
+                    frame.shown_at = now;
+                    self.request_redraw = true;
                 }
             }
-            KeyEvent {
-                code: KeyCode::Enter,
-                ..
-            } => {
-                if self.textarea_focused {
-                    if let Some(channel) = &self.active_tab {
-                        let text = self.textarea.lines().join(" ");
-                        let message = Message {
-                            channel: channel.clone(),
-                            username: self.cfg.username.to_string(),
-                            msg: text,
-                        };
-                        let _ = self.handler_tx.send(ev::Send::Message(message.clone()));
+        }
+    }
 
-                        if let Some(tab) = self.tabs.get_mut(channel) {
-                            tab.push_message(&self.mention_finder, message);
-                        }
-                    }
-                }
+    fn room_state_event(
+        &mut self,
+        channel: String,
+        slow: Option<Option<u64>>,
+        sub_only: Option<bool>,
+        emote_only: Option<bool>,
+        followers_only: Option<Option<u64>>,
+    ) {
+        if let Some(chat) = self.tabs.get_mut(&channel) {
+            let state = chat.room_state.get_or_insert_with(RoomState::default);
+            if let Some(slow) = slow {
+                state.slow = slow;
             }
-            _ => {
-                if self.textarea_focused {
-                    // TODO: abstract over crossterm Key
-                    self.textarea.input(key);
-                }
+            if let Some(sub_only) = sub_only {
+                state.sub_only = sub_only;
             }
-        };
+            if let Some(emote_only) = emote_only {
+                state.emote_only = emote_only;
+            }
+            if let Some(followers_only) = followers_only {
+                state.followers_only = followers_only;
+            }
+        }
+        if self.active_tab.as_ref() == Some(&channel) {
+            self.request_redraw = true;
+        }
+    }
 
-        self.request_redraw = true;
+    fn stream_status_event(&mut self, channel: String, status: Option<StreamStatus>) {
+        if let Some(chat) = self.tabs.get_mut(&channel) {
+            chat.stream_status = Some(status);
+        }
+        if self.active_tab.as_ref() == Some(&channel) {
+            self.request_redraw = true;
+        }
     }
 
-    fn message_event(&mut self, message: Message) {
-        if let Some(c) = self.tabs.get_mut(&message.channel) {
-            if self
-                .active_tab
-                .as_ref()
-                .is_some_and(|tab| tab == &message.channel)
-            {
-                self.request_redraw = true;
-            }
-            c.push_message(&self.mention_finder, message);
+    fn channel_update_event(
+        &mut self,
+        channel: String,
+        title: Option<String>,
+        category: Option<String>,
+    ) {
+        if let Some(title) = title {
+            self.message_event(Message::system(
+                channel.clone(),
+                format!("Stream title updated: {title}"),
+            ));
+        }
+        if let Some(category) = category {
+            self.message_event(Message::system(channel, format!("Now playing: {category}")));
         }
     }
 }
@@ -188,57 +3200,235 @@ pub struct Tui;
 
 impl Tui {
     pub async fn run(&mut self, cfg: Config, cache_dir: String) -> eyre::Result<()> {
+        // Checked before the terminal is taken over so config errors stay readable
+        let keymap = keybindings::keymap(&cfg.keybindings)?;
+        let theme = Theme::from_config(&cfg.theme)?;
+        let highlights = compile_highlights(&cfg)?;
+        // Builds the http client, which fails on a bad `proxy`
+        let cache = Cache::new(cache_dir.clone(), &cfg)?;
         Self::init_error_hooks()?;
-        let mut term = self.enter()?;
+        let mut term = self.enter(cfg.mouse_capture)?;
+        if let Some(style) = cfg.cursor_style {
+            crossterm::execute!(
+                std::io::stdout(),
+                match style {
+                    CursorStyle::Block => SetCursorStyle::SteadyBlock,
+                    CursorStyle::Underline => SetCursorStyle::SteadyUnderScore,
+                    CursorStyle::Bar => SetCursorStyle::SteadyBar,
+                }
+            )?;
+        }
+
+        // Query the terminal before the event reader starts consuming its replies
+        let picker = Picker::from_termios().ok().map(|mut picker| {
+            picker.guess_protocol();
+            picker
+        });
 
         let (event_tx, mut event_rx) = mpsc::unbounded_channel();
         let (handler_tx, handler_rx) = mpsc::unbounded_channel();
 
-        let mention_finder = memchr::memmem::Finder::new(cfg.username.as_ref());
-        let mut state = State::new(false, mention_finder, handler_tx, cfg.clone());
+        let mention_finders = cfg.mention_finders();
+        let mut state = State::new(
+            false,
+            mention_finders,
+            Emotes::new(picker),
+            handler_tx,
+            event_tx.clone(),
+            cfg.clone(),
+        );
+        state.keymap = keymap;
+        state.theme = theme;
+        state.highlights = highlights;
         state.active_tab = Some(cfg.channels.first().map_or("".into(), |s| s.to_string()));
 
         state.tabs = IndexMap::from_iter(
             cfg.channels
                 .iter()
-                .map(|c| (c.clone().into_owned(), Chat::new())),
+                .map(|c| (c.clone().into_owned(), Chat::new(&cfg))),
         );
+        // Chat reports channels in lowercase, and the main account already joins its own tabs
+        for channel in cfg.accounts.iter().flat_map(|account| &account.channels) {
+            if !state
+                .tabs
+                .keys()
+                .any(|tab| tab.eq_ignore_ascii_case(channel))
+            {
+                state.tabs.insert(channel.to_lowercase(), Chat::new(&cfg));
+            }
+        }
+
+        let drafts_path = Path::new(&cache_dir).join("drafts.json");
+        state.drafts = persist::load(&drafts_path);
+        state.restore_draft();
+
+        let history_path = Path::new(&cache_dir).join("history.json");
+        state.message_history = persist::load(&history_path);
+        state.message_history.truncate(MESSAGE_HISTORY_LEN);
+
+        let ignored_users_path = Path::new(&cache_dir).join("ignored_users.json");
+        state.ignored_users = persist::load::<HashSet<String>>(&ignored_users_path)
+            .into_iter()
+            .map(|username| username.to_lowercase())
+            .collect();
+
+        let gift_totals_path = Path::new(&cache_dir).join("gift_totals.json");
+        if cfg.persist_gift_stats {
+            state.gift_totals = persist::load(&gift_totals_path);
+        }
+
+        let emote_stats_path = Path::new(&cache_dir).join("emote_stats.json");
+        state.emote_counts = persist::load(&emote_stats_path);
 
         // Draw first frame early as possible
         term.draw(|frame| {
-            Self::render(frame, &state);
+            Self::render(frame, &mut state);
         })?;
 
+        if let Some(motd) = &cfg.motd {
+            for channel in &cfg.channels {
+                state.message_event(Message::system(channel.to_string(), motd.clone()));
+            }
+            let _ = event_tx.send(ev::In::Redraw);
+        }
+
+        let mut animation_interval = tokio::time::interval(ANIMATION_TICK);
+        animation_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
         {
             let cfg = cfg.clone();
             tokio::spawn(async move {
-                let mut handler = EventHandler::new(cfg, cache_dir, event_tx, handler_rx);
+                let mut handler = EventHandler::new(cfg, cache, event_tx, handler_rx);
                 handler.run().await.unwrap();
             })
         };
 
         loop {
+            let raid_popup_deadline = state
+                .raid_popup
+                .as_ref()
+                .map_or_else(Instant::now, |(deadline, _)| *deadline);
+            let gift_bomb_deadline = state
+                .gift_bomb
+                .as_ref()
+                .map_or_else(Instant::now, |bomb| bomb.deadline);
+
             select! {
+                _ = tokio::time::sleep_until(raid_popup_deadline.into()), if state.raid_popup.is_some() => {
+                    state.raid_popup = None;
+                    state.request_redraw = true;
+                }
+                _ = tokio::time::sleep_until(gift_bomb_deadline.into()), if state.gift_bomb.is_some() => {
+                    state.gift_bomb = None;
+                    state.request_redraw = true;
+                }
+                _ = animation_interval.tick(), if state.is_animating() => {
+                    state.animation_tick_event();
+                }
                 Some(e) = event_rx.recv() => {
                     match e {
                         ev::In::Key(k) => {
                             state.key_event(k);
                         }
+                        ev::In::Mouse(m) => state.mouse_event(m),
                         ev::In::Message(message) => {
                             state.message_event(message);
                         }
+                        ev::In::ChannelUpdate { channel, title, category } => {
+                            state.channel_update_event(channel, title, category);
+                        }
+                        ev::In::PruneMessages => state.prune_messages_event(),
+                        ev::In::Schedule { channel, segments } => {
+                            state.schedule_event(channel, segments);
+                        }
+                        ev::In::Gift { gifter, count, .. } => state.gift_event(gifter, count),
+                        ev::In::Whisper { sender, text } => state.whisper_event(sender, text),
+                        ev::In::PredictionEnd(prediction) => state.prediction_end_event(prediction),
+                        ev::In::StreamInfoUpdated(info) => state.stream_info_event(info),
+                        ev::In::StreamDelay { channel, delay } => {
+                            state.stream_delay_event(channel, delay);
+                        }
+                        ev::In::RoomState {
+                            channel,
+                            slow,
+                            sub_only,
+                            emote_only,
+                            followers_only,
+                        } => {
+                            state.room_state_event(
+                                channel,
+                                slow,
+                                sub_only,
+                                emote_only,
+                                followers_only,
+                            );
+                        }
+                        ev::In::StreamStatus { channel, status } => {
+                            state.stream_status_event(channel, status);
+                        }
+                        ev::In::TeamMemberLive { login } => state.team_member_live_event(login),
+                        ev::In::CommunityGiftSub { gifter, .. } => {
+                            state.community_gift_sub_event(gifter);
+                        }
+                        ev::In::Raid { raider, viewers, .. } => state.raid_event(raider, viewers),
+                        ev::In::SystemEvent(event) => state.message_event(event.into_message()),
+                        ev::In::MessageDeleted { channel, id } => {
+                            state.message_deleted_event(channel, id);
+                        }
+                        ev::In::UserCleared { channel, user, timeout } => {
+                            state.user_cleared_event(channel, user, timeout);
+                        }
+                        ev::In::CategoryStreams { category, streams } => {
+                            state.category_streams_event(category, streams);
+                        }
+                        ev::In::Charity { channel, campaign } => {
+                            state.charity_event(channel, campaign);
+                        }
+                        ev::In::ChannelSuggestions { query, channels } => {
+                            state.channel_suggestions_event(query, channels);
+                        }
+                        ev::In::Emotes(emotes) => state.emotes.insert(emotes),
+                        ev::In::Drops(campaigns) => state.drops_event(campaigns),
+                        ev::In::Rewards { channel, rewards } => {
+                            state.rewards_event(channel, rewards);
+                        }
+                        ev::In::EventSubHealth { degraded } => {
+                            state.request_redraw = state.eventsub_degraded != degraded;
+                            state.eventsub_degraded = degraded;
+                        }
+                        ev::In::Notification(text) => {
+                            if let Some(channel) = state.active_tab.clone() {
+                                state.message_event(Message::system(channel, text));
+                            }
+                        }
+                        ev::In::Task { name, state: task, at } => {
+                            state.task_event(name, task, at);
+                        }
                         ev::In::Redraw => state.request_redraw = true,
+                        ev::In::ConfigReload(cfg) => state.config_reload_event(cfg),
+                        ev::In::CacheStats(stats) => {
+                            state.cache_stats = Some(stats);
+                            state.request_redraw |= state.task_panel;
+                        }
                     }
                 }
             }
 
             if state.quit {
+                state.save_draft();
+                persist::save(&drafts_path, &state.drafts)?;
+                persist::save(&history_path, &state.message_history)?;
+                persist::save(&ignored_users_path, &state.ignored_users)?;
+                if cfg.persist_gift_stats {
+                    persist::save(&gift_totals_path, &state.gift_totals)?;
+                }
+                persist::save(&emote_stats_path, &state.emote_counts)?;
                 break;
             }
 
             if state.request_redraw {
                 term.draw(|frame| {
-                    Self::render(frame, &state);
+                    Self::render(frame, &mut state);
                 })?;
 
                 state.request_redraw = false;
@@ -263,42 +3453,597 @@ impl Tui {
         Ok(())
     }
 
-    fn enter(&self) -> eyre::Result<Terminal<CrosstermBackend<Stdout>>> {
+    fn enter(&self, mouse_capture: bool) -> eyre::Result<Terminal<CrosstermBackend<Stdout>>> {
         let backend = CrosstermBackend::new(std::io::stdout());
         let term = Terminal::new(backend)?;
         terminal::enable_raw_mode()?;
         crossterm::execute!(std::io::stdout(), terminal::EnterAlternateScreen)?;
+        if mouse_capture {
+            crossterm::execute!(std::io::stdout(), crossterm::event::EnableMouseCapture)?;
+        }
         Ok(term)
     }
 
     fn leave() -> eyre::Result<()> {
-        crossterm::execute!(std::io::stdout(), terminal::LeaveAlternateScreen)?;
+        crossterm::execute!(
+            std::io::stdout(),
+            SetCursorStyle::DefaultUserShape,
+            crossterm::event::DisableMouseCapture,
+            terminal::LeaveAlternateScreen
+        )?;
         terminal::disable_raw_mode()?;
         Ok(())
     }
 
-    fn render(frame: &mut Frame, state: &State) {
+    /// Moves the terminal cursor to where the textarea drew its cursor and erases the drawn one,
+    /// the textarea doesn't expose its scroll position to compute it directly
+    fn use_terminal_cursor(frame: &mut Frame, area: Rect) {
+        let buf = frame.buffer_mut();
+        let cursor = area.positions().find(|&position| {
+            buf.cell(position)
+                .is_some_and(|cell| cell.modifier.contains(Modifier::REVERSED))
+        });
+        if let Some(position) = cursor {
+            if let Some(cell) = buf.cell_mut(position) {
+                cell.modifier.remove(Modifier::REVERSED);
+            }
+            frame.set_cursor_position(position);
+        }
+    }
+
+    /// Centered over everything else
+    fn render_modal(frame: &mut Frame, modal: &Modal) {
+        let area = frame.area();
+        let width = (area.width / 2).max(30).min(area.width);
+        let height = match modal {
+            // Room for a wrapped line or two
+            Modal::Alert(text) => (text.lines().count() as u16 + 2).max(5),
+            Modal::Confirm { .. } => 5,
+            Modal::Picker { .. } => PICKER_HEIGHT + 3,
+        }
+        .min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        frame.render_widget(Clear, popup);
+
+        match modal {
+            Modal::Alert(text) => frame.render_widget(
+                Paragraph::new(text.as_str())
+                    .wrap(Wrap { trim: false })
+                    .block(
+                        Block::bordered()
+                            .title("[any key] close")
+                            .title_alignment(Alignment::Center)
+                            .red(),
+                    ),
+                popup,
+            ),
+            Modal::Confirm { message, .. } => frame.render_widget(
+                Paragraph::new(message.as_str())
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: false })
+                    .block(
+                        Block::bordered()
+                            .title("[y] yes [n] no")
+                            .title_alignment(Alignment::Center),
+                    ),
+                popup,
+            ),
+            Modal::Picker {
+                title,
+                items,
+                query,
+                selected,
+                ..
+            } => {
+                let block = Block::bordered()
+                    .title(format!("{title} [Enter] pick [Esc] close"))
+                    .title_alignment(Alignment::Center);
+                let inner = block.inner(popup);
+                frame.render_widget(block, popup);
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(1), Constraint::Fill(1)])
+                    .split(inner);
+                frame.render_widget(Paragraph::new(format!("> {query}")), rows[0]);
+                let matches = picker_matches(items, query).map(|item| Line::from(item.as_str()));
+                let mut list_state = ListState::default().with_selected(Some(*selected));
+                frame.render_stateful_widget(
+                    List::new(matches).highlight_style(Style::default().reversed()),
+                    rows[1],
+                    &mut list_state,
+                );
+            }
+        }
+    }
+
+    fn render_search(frame: &mut Frame, state: &State, search: &Search) {
+        let area = frame.area();
+        let popup = Rect {
+            x: area.x + area.width / 10,
+            y: area.y + area.height / 10,
+            width: area.width * 8 / 10,
+            height: area.height * 8 / 10,
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Fill(1)])
+            .split(popup);
+
+        let results = state.search_results(&search.query);
+        let items = results.iter().map(|(channel, message)| {
+            Line::from(vec![
+                Span::styled(format!("{channel} "), Style::default().dim()),
+                Span::styled(
+                    message.username.clone(),
+                    Style::default().fg(state.theme.username.unwrap_or(Color::Blue)),
+                ),
+                Span::raw(": "),
+                Span::raw(message.msg.clone()),
+            ])
+        });
+        let mut list_state = ListState::default()
+            .with_selected(Some(search.selected.min(results.len().saturating_sub(1))));
+
+        frame.render_widget(Clear, popup);
+        frame.render_widget(
+            Paragraph::new(search.query.as_str()).block(
+                Block::bordered()
+                    .title("Search all channels")
+                    .title_alignment(Alignment::Center),
+            ),
+            chunks[0],
+        );
+        frame.render_stateful_widget(
+            List::new(items)
+                .highlight_style(Style::default().reversed())
+                .block(Block::bordered().title(format!("{} results", results.len()))),
+            chunks[1],
+            &mut list_state,
+        );
+    }
+
+    fn render(frame: &mut Frame, state: &mut State) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(90), Constraint::Percentage(10)])
+            .constraints([
+                Constraint::Percentage(u16::from(state.cfg.chat_height_percent) - 2),
+                Constraint::Min(1),
+                Constraint::Percentage(100 - u16::from(state.cfg.chat_height_percent)),
+            ])
             .split(frame.area());
+        frame.render_widget(state.status_bar(), chunks[1]);
+        let chunks = [chunks[0], chunks[2]];
+
+        let query = state
+            .buffer_search
+            .as_ref()
+            .map(|query| query.to_lowercase());
+        let filter = LineFilter {
+            emote_only: state.emote_only_view,
+            query: query.as_deref(),
+        };
+
+        if let Some(query) = &state.buffer_search {
+            frame.render_widget(
+                Paragraph::new(format!("/{query}")).block(
+                    Block::bordered()
+                        .border_style(state.theme.border)
+                        .title("Filter [Esc] close")
+                        .title_alignment(Alignment::Center),
+                ),
+                chunks[1],
+            );
+        } else {
+            state
+                .textarea
+                .set_block(Block::bordered().border_style(state.theme.border));
+            frame.render_widget(&state.textarea, chunks[1]);
+            if state.cfg.cursor_style.is_some() {
+                Self::use_terminal_cursor(frame, chunks[1]);
+            }
+        }
+
+        let mut chunks = chunks.to_vec();
+        if let Some(status) = state.status_line() {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Fill(1), Constraint::Length(1)])
+                .split(chunks[0]);
+            frame.render_widget(status, rows[1]);
+            chunks[0] = rows[0];
+        }
 
-        frame.render_widget(&state.textarea, chunks[1]);
         let active = state.active_tab.clone().unwrap_or("".into());
 
-        let mut tabs = Block::bordered().title_alignment(Alignment::Center);
-        for (name, _) in &state.tabs {
-            tabs = tabs.title(if name == &active {
-                name.clone().into()
-            } else {
-                name.clone().dim()
+        state.channel_list_area = None;
+        if state.channel_list {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Length(state.cfg.sidebar_width),
+                    Constraint::Fill(1),
+                ])
+                .split(chunks[0]);
+            let items = state.tabs.iter().map(|(name, chat)| match chat.unseen {
+                0 => Line::from(name.as_str()),
+                unseen => Line::from(vec![
+                    Span::raw(name.as_str()),
+                    Span::raw(format!(" ({unseen})")).yellow(),
+                ]),
             });
+            let mut list_state =
+                ListState::default().with_selected(state.tabs.get_index_of(&active));
+            frame.render_stateful_widget(
+                List::new(items)
+                    .block(
+                        Block::bordered()
+                            .border_style(state.theme.border)
+                            .title("Channels"),
+                    )
+                    .highlight_style(Style::default().reversed()),
+                columns[0],
+                &mut list_state,
+            );
+            state.channel_list_area = Some((columns[0], list_state.offset()));
+            chunks[0] = columns[1];
+        }
+
+        let mut tabs = Block::bordered()
+            .border_style(state.theme.border)
+            .title_alignment(Alignment::Center);
+        let mut titles = Vec::new();
+        for (name, chat) in &state.tabs {
+            let title: Line = if name == WHISPERS_TAB && state.whisper_flash() {
+                name.clone().light_yellow().bold().into()
+            } else if name == &active {
+                match chat.room_state_label() {
+                    Some(label) => format!("{name} {label}").into(),
+                    None => name.clone().into(),
+                }
+            } else {
+                name.clone().dim().into()
+            };
+            titles.push((name.clone(), title.width() as u16));
+            tabs = tabs.title(title);
         }
 
+        let mut emote_area = None;
+        let mut secondary_emote_area = None;
         if let Some(active_chat) = state.tabs.get(&active) {
-            frame.render_widget(active_chat.list(active).block(tabs), chunks[0]);
+            let mut chat_area = chunks[0];
+            if let Some(gauge) = active_chat.charity_gauge() {
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(3), Constraint::Fill(1)])
+                    .split(chat_area);
+                frame.render_widget(gauge, rows[0]);
+                chat_area = rows[1];
+            }
+
+            state.user_list_area = None;
+            if state.user_list {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([
+                        Constraint::Fill(1),
+                        Constraint::Length(state.cfg.user_list_width),
+                    ])
+                    .split(chat_area);
+                let users = active_chat.user_list(state.cfg.user_list_ttl());
+                state.user_list_scroll = state.user_list_scroll.min(users.len().saturating_sub(1));
+                let title = format!("Chatters ({})", users.len());
+                frame.render_stateful_widget(
+                    List::new(users).block(
+                        Block::bordered()
+                            .border_style(state.theme.border)
+                            .title(title),
+                    ),
+                    columns[1],
+                    &mut ListState::default().with_offset(state.user_list_scroll),
+                );
+                state.user_list_area = Some(columns[1]);
+                chat_area = columns[0];
+            }
+
+            let chat_area = if let Some(panel) = state.side_panel {
+                let panels = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .split(chat_area);
+                match panel {
+                    SidePanel::Schedule => {
+                        frame.render_widget(active_chat.schedule_table(), panels[1])
+                    }
+                    SidePanel::Leaderboard => {
+                        let tables = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                            .split(panels[1]);
+                        frame.render_widget(state.leaderboard(), tables[0]);
+                        frame.render_widget(state.emote_leaderboard(), tables[1]);
+                    }
+                    SidePanel::Streams => State::render_streams(frame, panels[1], state),
+                    SidePanel::Drops => State::render_drops(frame, panels[1], state),
+                    SidePanel::Rewards => State::render_rewards(frame, panels[1], state),
+                    SidePanel::Predictions => {
+                        frame.render_widget(state.prediction_history(), panels[1])
+                    }
+                }
+                panels[0]
+            } else {
+                chat_area
+            };
+
+            let secondary = state
+                .secondary_active_tab
+                .as_ref()
+                .filter(|_| state.split_mode)
+                .and_then(|tab| state.tabs.get_key_value(tab));
+            let chat_area = if let Some((name, chat)) = secondary {
+                let panes = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(chat_area);
+                let (focused, other) = if state.focus_right {
+                    (panes[1], panes[0])
+                } else {
+                    (panes[0], panes[1])
+                };
+                let block = Block::bordered()
+                    .border_style(state.theme.border)
+                    .title(name.clone().dim())
+                    .title_alignment(Alignment::Center);
+                let inner = block.inner(other);
+                let mut list_state =
+                    ListState::default().with_offset(chat.scroll_offset().round() as usize);
+                frame.render_stateful_widget(
+                    chat.list(name.clone(), state.animation_frame(), filter)
+                        .block(block),
+                    other,
+                    &mut list_state,
+                );
+                secondary_emote_area = Some((name.clone(), inner, list_state.offset()));
+                focused
+            } else {
+                chat_area
+            };
+
+            // Selection indexes every line, so it's hidden while lines are filtered
+            let mut list_state = ListState::default()
+                .with_offset(active_chat.scroll_offset().round() as usize)
+                .with_selected(active_chat.selected.filter(|_| !filter.is_active()));
+            if active_chat.unread > 0 {
+                tabs = tabs.title_bottom(
+                    Line::from(format!(" {} new message(s) ↓ ", active_chat.unread))
+                        .yellow()
+                        .right_aligned(),
+                );
+            }
+            let inner = tabs.inner(chat_area);
+            state.tab_title_areas = tab_title_areas(chat_area, titles);
+            frame.render_stateful_widget(
+                active_chat
+                    .list(active.clone(), state.animation_frame(), filter)
+                    .block(tabs)
+                    .highlight_style(Style::default().reversed()),
+                chat_area,
+                &mut list_state,
+            );
+            emote_area = Some((inner, list_state.offset()));
         } else {
+            state.tab_title_areas = tab_title_areas(chunks[0], titles);
             frame.render_widget(tabs, chunks[0]);
         }
+
+        if let (Some((area, offset)), Some(chat)) = (emote_area, state.tabs.get_mut(&active)) {
+            chat.render_emotes(
+                frame,
+                area,
+                offset,
+                filter,
+                &mut state.emotes,
+                &state.emote_frames,
+            );
+        }
+        if let Some((name, area, offset)) = secondary_emote_area {
+            if let Some(chat) = state.tabs.get_mut(&name) {
+                chat.render_emotes(
+                    frame,
+                    area,
+                    offset,
+                    filter,
+                    &mut state.emotes,
+                    &state.emote_frames,
+                );
+            }
+        }
+
+        if state.task_panel {
+            let area = frame.area();
+            let stats = state.cache_stats.as_ref().map(CacheStats::summary);
+            let stats_height = stats
+                .as_ref()
+                .map_or(0, |stats| stats.lines().count() as u16 + 2);
+            let height = (state.tasks.len() as u16 + 3 + stats_height).min(area.height);
+            let popup = Rect {
+                x: area.x + area.width / 8,
+                y: area.y + (area.height - height) / 2,
+                width: area.width * 3 / 4,
+                height,
+            };
+            let [tasks_area, stats_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(stats_height)])
+                    .areas(popup);
+            frame.render_widget(Clear, popup);
+            frame.render_widget(state.task_table(), tasks_area);
+            if let Some(stats) = stats {
+                frame.render_widget(
+                    Paragraph::new(stats).block(
+                        Block::bordered()
+                            .title("Cache")
+                            .title_alignment(Alignment::Center),
+                    ),
+                    stats_area,
+                );
+            }
+        }
+
+        if state.debug_view {
+            let height = 8.min(chunks[0].height);
+            let area = Rect {
+                y: chunks[0].bottom() - height,
+                height,
+                ..chunks[0]
+            };
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(state.debug_lines())
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title("Debug")),
+                area,
+            );
+        }
+
+        if !state.channel_suggestions.is_empty() {
+            let height = (state.channel_suggestions.len() as u16 + 2).min(chunks[0].height);
+            let area = Rect {
+                x: chunks[0].x,
+                y: chunks[0].bottom() - height,
+                width: 30.min(chunks[0].width),
+                height,
+            };
+            let items = state
+                .channel_suggestions
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    let line = Line::from(format!("#{name}"));
+                    if state.suggestion_index == Some(i) {
+                        line.reversed()
+                    } else {
+                        line
+                    }
+                });
+            frame.render_widget(Clear, area);
+            frame.render_widget(List::new(items).block(Block::bordered()), area);
+        }
+
+        if let Some(autocomplete) = &state.autocomplete {
+            let height = (autocomplete.candidates.len() as u16 + 2).min(chunks[0].height);
+            let area = Rect {
+                x: chunks[0].x,
+                y: chunks[0].bottom() - height,
+                width: 30.min(chunks[0].width),
+                height,
+            };
+            let items = autocomplete.candidates.iter().enumerate().map(|(i, name)| {
+                let line = Line::from(name.as_str());
+                if i == autocomplete.selected {
+                    line.reversed()
+                } else {
+                    line
+                }
+            });
+            frame.render_widget(Clear, area);
+            frame.render_widget(List::new(items).block(Block::bordered()), area);
+        }
+
+        if let Some(search) = &state.search {
+            Self::render_search(frame, state, search);
+        }
+
+        if let Some(menu) = &state.context_menu {
+            let area = frame.area();
+            let longest = ContextMenu::OPTIONS.iter().map(|option| option.len()).max();
+            let width = (longest.unwrap_or_default() as u16 + 2).min(area.width);
+            let height = (ContextMenu::OPTIONS.len() as u16 + 2).min(area.height);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + (area.height - height) / 2,
+                width,
+                height,
+            };
+            frame.render_widget(Clear, popup);
+            frame.render_widget(
+                List::new(ContextMenu::OPTIONS).block(
+                    Block::bordered()
+                        .title(menu.username.as_str())
+                        .title_alignment(Alignment::Center),
+                ),
+                popup,
+            );
+        }
+
+        if let Some(bomb) = &state.gift_bomb {
+            let area = frame.area();
+            let width = 40.min(area.width);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + 1,
+                width,
+                height: 3.min(area.height.saturating_sub(1)),
+            };
+            frame.render_widget(Clear, popup);
+            frame.render_widget(
+                Gauge::default()
+                    .block(
+                        Block::bordered()
+                            .title(format!("🎁 {} gift bomb", bomb.gifter))
+                            .magenta(),
+                    )
+                    .gauge_style(Style::default().magenta())
+                    .ratio((bomb.received as f64 / bomb.total as f64).min(1.0))
+                    .label(format!("{}/{}", bomb.received, bomb.total)),
+                popup,
+            );
+        }
+
+        if let Some((_, text)) = state
+            .whisper_alert
+            .as_ref()
+            .filter(|(received, _)| received.elapsed() < WHISPER_ALERT_DURATION)
+        {
+            let area = frame.area();
+            let width = (text.chars().count() as u16 + 4).min(area.width / 2);
+            let toast = Rect {
+                x: area.right() - width,
+                y: area.y,
+                width,
+                height: 3.min(area.height),
+            };
+            frame.render_widget(Clear, toast);
+            frame.render_widget(
+                Paragraph::new(text.as_str()).block(Block::bordered().light_yellow()),
+                toast,
+            );
+        }
+
+        if let Some((_, text)) = &state.raid_popup {
+            let area = frame.area();
+            let width = (text.chars().count() as u16 + 4).min(area.width);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + area.height.saturating_sub(3) / 2,
+                width,
+                height: 3.min(area.height),
+            };
+            frame.render_widget(Clear, popup);
+            frame.render_widget(
+                Paragraph::new(text.as_str())
+                    .alignment(Alignment::Center)
+                    .bold()
+                    .block(Block::bordered().red()),
+                popup,
+            );
+        }
+
+        if let Some(modal) = &state.modal {
+            Self::render_modal(frame, modal);
+        }
     }
 }